@@ -0,0 +1,504 @@
+use alloc::alloc::{GlobalAlloc, Layout};
+use core::ptr::{self, null_mut};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use spin::Mutex;
+use x86_64::structures::paging::mapper::MapToError;
+use x86_64::structures::paging::{Page, PageSize, Size4KiB};
+use x86_64::VirtAddr;
+
+mod slab;
+
+use self::slab::SlabCaches;
+pub use self::slab::{SlabStats, SLAB_SIZES};
+
+// 堆的虚拟地址，远离内核映像和bootloader映射的物理内存
+pub const HEAP_START: usize = 0x_4444_4444_0000;
+pub const HEAP_SIZE: usize = 100 * 1024; // 100 KiB，启动时映射的初始大小
+/// 堆默认最多增长到的大小
+pub const DEFAULT_HEAP_LIMIT: usize = 16 * 1024 * 1024;
+// 堆每次至少增长的大小
+const HEAP_GROWTH_MIN: usize = 64 * 1024;
+
+// 空闲链表的节点，放在每个空闲块的开头
+struct ListNode {
+    size: usize,
+    next: *mut ListNode,
+}
+
+// 节点的大小和对齐，也是分配块的最小大小和最小对齐
+const NODE_SIZE: usize = core::mem::size_of::<ListNode>();
+const NODE_ALIGN: usize = core::mem::align_of::<ListNode>();
+
+// 按地址排序的空闲链表，释放时与相邻的空闲块合并
+struct FreeList {
+    head: *mut ListNode,
+}
+
+// 链表只通过LinkedListAllocator中的锁访问
+unsafe impl Send for FreeList {}
+
+impl FreeList {
+    const fn new() -> Self {
+        FreeList { head: null_mut() }
+    }
+
+    // 把[addr, addr + size)放回链表，与前后相邻的空闲块合并
+    unsafe fn insert(&mut self, addr: usize, size: usize) {
+        debug_assert!(size >= NODE_SIZE && addr.is_multiple_of(NODE_ALIGN) && size.is_multiple_of(NODE_ALIGN));
+
+        let mut prev: *mut ListNode = null_mut();
+        let mut next = self.head;
+        while !next.is_null() && (next as usize) < addr {
+            prev = next;
+            next = (*next).next;
+        }
+
+        let node = addr as *mut ListNode;
+        ptr::write(node, ListNode { size, next });
+        if !next.is_null() && addr + size == next as usize {
+            (*node).size += (*next).size;
+            (*node).next = (*next).next;
+        }
+
+        if prev.is_null() {
+            self.head = node;
+        } else if prev as usize + (*prev).size == addr {
+            (*prev).size += (*node).size;
+            (*prev).next = (*node).next;
+        } else {
+            (*prev).next = node;
+        }
+    }
+
+    // 找到第一个放得下的空闲块并从中切出分配的块，对齐前后剩余的部分放回链表
+    unsafe fn allocate(&mut self, size: usize, align: usize) -> Option<usize> {
+        let mut prev: *mut ListNode = null_mut();
+        let mut current = self.head;
+        while !current.is_null() {
+            let start = current as usize;
+            let end = start + (*current).size;
+            if let Some(alloc_start) = fit(start, end, size, align) {
+                let next = (*current).next;
+                if prev.is_null() {
+                    self.head = next;
+                } else {
+                    (*prev).next = next;
+                }
+
+                let alloc_end = alloc_start + size;
+                if alloc_start > start {
+                    self.insert(start, alloc_start - start);
+                }
+                if end > alloc_end {
+                    self.insert(alloc_end, end - alloc_end);
+                }
+                return Some(alloc_start);
+            }
+            prev = current;
+            current = (*current).next;
+        }
+        None
+    }
+
+    // 遍历所有空闲块的大小
+    fn sizes(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut current = self.head;
+        core::iter::from_fn(move || {
+            if current.is_null() {
+                return None;
+            }
+            let node = unsafe { &*current };
+            current = node.next;
+            Some(node.size)
+        })
+    }
+}
+
+// 在空闲块[start, end)中放置一个按align对齐的size字节的块，返回块的起始地址
+// 前后剩余的部分要么为空，要么能放下一个节点，否则这部分内存就无法再回到链表中
+fn fit(start: usize, end: usize, size: usize, align: usize) -> Option<usize> {
+    let mut alloc_start = align_up(start, align);
+    if alloc_start != start && alloc_start - start < NODE_SIZE {
+        alloc_start = align_up(start + NODE_SIZE, align);
+    }
+    let alloc_end = alloc_start.checked_add(size)?;
+    if alloc_end > end {
+        return None;
+    }
+    let excess = end - alloc_end;
+    if excess != 0 && excess < NODE_SIZE {
+        return None;
+    }
+    Some(alloc_start)
+}
+
+// 一次分配实际占用的块大小和对齐：不小于一个节点，并且是节点对齐的整数倍，
+// 释放时由同样的Layout算出完全相同的块范围
+fn block_layout(layout: Layout) -> (usize, usize) {
+    let size = align_up(layout.size().max(NODE_SIZE), NODE_ALIGN);
+    let align = layout.align().max(NODE_ALIGN);
+    (size, align)
+}
+
+// 类别的块在空闲链表中实际占用的大小，缓存命中和缓存释放按它统计，与未命中时一致
+fn class_block_size(class: usize) -> usize {
+    block_layout(slab::class_layout(class)).0
+}
+
+/// 堆分配器：小块由slab缓存分配，大块和缓存未命中时从按地址排序的空闲链表分配
+pub struct LinkedListAllocator {
+    free_list: Mutex<FreeList>,
+    slabs: Mutex<SlabCaches>,
+    // 各类别slab缓存的命中和未命中次数
+    slab_hits: [AtomicU64; SLAB_SIZES.len()],
+    slab_misses: [AtomicU64; SLAB_SIZES.len()],
+    // 已映射的堆的结束地址和大小，空闲链表放不下时在结束地址之后继续映射
+    heap_end: AtomicUsize,
+    heap_size: AtomicUsize,
+    // 堆最多增长到的大小
+    heap_limit: AtomicUsize,
+    // 内存统计
+    growth_count: AtomicU64,
+    growth_failures: AtomicU64,
+    total_allocated: AtomicU64,
+    total_freed: AtomicU64,
+    allocation_count: AtomicU64,
+    deallocation_count: AtomicU64,
+    current_allocated: AtomicU64,
+    max_allocated: AtomicU64,
+}
+
+impl LinkedListAllocator {
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        LinkedListAllocator { 
+            free_list: Mutex::new(FreeList::new()),
+            slabs: Mutex::new(SlabCaches::new()),
+            slab_hits: [ZERO; SLAB_SIZES.len()],
+            slab_misses: [ZERO; SLAB_SIZES.len()],
+            heap_end: AtomicUsize::new(0),
+            heap_size: AtomicUsize::new(0),
+            heap_limit: AtomicUsize::new(DEFAULT_HEAP_LIMIT),
+            growth_count: AtomicU64::new(0),
+            growth_failures: AtomicU64::new(0),
+            total_allocated: AtomicU64::new(0),
+            total_freed: AtomicU64::new(0),
+            allocation_count: AtomicU64::new(0),
+            deallocation_count: AtomicU64::new(0),
+            current_allocated: AtomicU64::new(0),
+            max_allocated: AtomicU64::new(0),
+        }
+    }
+
+    /// 把[heap_start, heap_start + heap_size)加入空闲链表，调用者必须保证这段内存已映射且未被使用
+    pub unsafe fn init(&self, heap_start: usize, heap_size: usize) {
+        let start = align_up(heap_start, NODE_ALIGN);
+        let end = (heap_start + heap_size) & !(NODE_ALIGN - 1);
+        assert!(end >= start + NODE_SIZE, "heap region too small");
+        self.with_free_list(|list| list.insert(start, end - start));
+        self.heap_end.store(heap_start + heap_size, Ordering::Relaxed);
+        self.heap_size.store(heap_size, Ordering::Relaxed);
+    }
+
+    /// 堆最多增长到的大小
+    pub fn get_heap_limit(&self) -> usize {
+        self.heap_limit.load(Ordering::Relaxed)
+    }
+
+    /// 设置堆最多增长到的大小，已经映射的部分不会释放，所以上限不能小于当前的堆大小；
+    /// 返回是否设置成功
+    pub fn set_heap_limit(&self, limit: usize) -> bool {
+        // 堆只在持有空闲链表锁时增长，在锁内检查和设置，不会和增长交错
+        self.with_free_list(|_| {
+            if limit < self.heap_size.load(Ordering::Relaxed) {
+                return false;
+            }
+            self.heap_limit.store(limit, Ordering::Relaxed);
+            true
+        })
+    }
+
+    // 空闲链表放不下size字节的块时，在堆的末尾映射新的页并加入链表，返回是否增长了
+    unsafe fn grow(&self, list: &mut FreeList, size: usize, align: usize) -> bool {
+        let heap_size = self.heap_size.load(Ordering::Relaxed);
+        let page_size = Size4KiB::SIZE as usize;
+        // 留出对齐和分割剩余部分可能需要的空间，并且不超过上限
+        let wanted = align_up((size + align + NODE_SIZE).max(HEAP_GROWTH_MIN), page_size);
+        let grow_by = wanted.min(self.get_heap_limit().saturating_sub(heap_size) / page_size * page_size);
+
+        // 逐页映射，中途失败时保留已经映射的部分
+        let start = self.heap_end.load(Ordering::Relaxed);
+        let mut mapped = 0;
+        while mapped < grow_by {
+            let page = Page::containing_address(VirtAddr::new((start + mapped) as u64));
+            if crate::memory::map_page(page).is_err() {
+                break;
+            }
+            mapped += page_size;
+        }
+
+        if mapped < grow_by || grow_by == 0 {
+            self.growth_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        if mapped == 0 {
+            return false;
+        }
+        list.insert(start, mapped);
+        self.heap_end.store(start + mapped, Ordering::Relaxed);
+        self.heap_size.store(heap_size + mapped, Ordering::Relaxed);
+        self.growth_count.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    fn with_free_list<R>(&self, f: impl FnOnce(&mut FreeList) -> R) -> R {
+        with_lock(&self.free_list, f)
+    }
+
+    // 内存统计方法
+    pub fn get_total_allocated(&self) -> u64 {
+        self.total_allocated.load(Ordering::Relaxed)
+    }
+
+    pub fn get_total_freed(&self) -> u64 {
+        self.total_freed.load(Ordering::Relaxed)
+    }
+
+    pub fn get_current_allocated(&self) -> u64 {
+        self.current_allocated.load(Ordering::Relaxed)
+    }
+
+    pub fn get_max_allocated(&self) -> u64 {
+        self.max_allocated.load(Ordering::Relaxed)
+    }
+
+    pub fn get_allocation_count(&self) -> u64 {
+        self.allocation_count.load(Ordering::Relaxed)
+    }
+
+    pub fn get_deallocation_count(&self) -> u64 {
+        self.deallocation_count.load(Ordering::Relaxed)
+    }
+
+    pub fn get_free_memory(&self) -> u64 {
+        self.with_free_list(|list| list.sizes().sum::<usize>() as u64)
+    }
+
+    /// 空闲块的数量和最大空闲块的大小
+    pub fn get_free_blocks(&self) -> (u64, u64) {
+        self.with_free_list(|list| {
+            list.sizes()
+                .fold((0, 0), |(count, largest), size| (count + 1, largest.max(size as u64)))
+        })
+    }
+
+    /// 各类别slab缓存的统计
+    pub fn get_slab_stats(&self) -> [SlabStats; SLAB_SIZES.len()] {
+        let cached: [usize; SLAB_SIZES.len()] = with_lock(&self.slabs, |slabs| core::array::from_fn(|class| slabs.cached(class)));
+        core::array::from_fn(|class| SlabStats {
+            block_size: SLAB_SIZES[class],
+            hits: self.slab_hits[class].load(Ordering::Relaxed),
+            misses: self.slab_misses[class].load(Ordering::Relaxed),
+            cached: cached[class],
+        })
+    }
+
+    pub fn get_memory_stats(&self) -> MemoryStats {
+        let (free_blocks, largest_free_block) = self.get_free_blocks();
+        MemoryStats {
+            total_heap_size: self.heap_size.load(Ordering::Relaxed) as u64,
+            heap_limit: self.get_heap_limit() as u64,
+            growth_count: self.growth_count.load(Ordering::Relaxed),
+            growth_failures: self.growth_failures.load(Ordering::Relaxed),
+            allocated: self.get_total_allocated(),
+            freed: self.get_total_freed(),
+            current_allocated: self.get_current_allocated(),
+            max_allocated: self.get_max_allocated(),
+            allocation_count: self.get_allocation_count(),
+            deallocation_count: self.get_deallocation_count(),
+            free_memory: self.get_free_memory(),
+            free_blocks,
+            largest_free_block,
+        }
+    }
+}
+
+/// 映射堆的初始页并初始化分配器，必须在页表和帧分配器初始化之后、第一次堆分配之前调用
+pub fn init_heap(allocator: &LinkedListAllocator) -> Result<(), MapToError<Size4KiB>> {
+    let heap_start = VirtAddr::new(HEAP_START as u64);
+    let heap_end = heap_start + (HEAP_SIZE - 1);
+    let pages = Page::range_inclusive(Page::containing_address(heap_start), Page::containing_address(heap_end));
+
+    for page in pages {
+        crate::memory::map_page(page)?;
+    }
+
+    unsafe { allocator.init(HEAP_START, HEAP_SIZE) };
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryStats {
+    /// 当前已映射的堆大小，随堆增长而变大
+    pub total_heap_size: u64,
+    /// 堆最多增长到的大小
+    pub heap_limit: u64,
+    /// 堆增长的次数
+    pub growth_count: u64,
+    /// 因为达到上限或没有物理帧而没能按需增长的次数
+    pub growth_failures: u64,
+    pub allocated: u64,
+    pub freed: u64,
+    pub current_allocated: u64,
+    pub max_allocated: u64,
+    pub allocation_count: u64,
+    pub deallocation_count: u64,
+    pub free_memory: u64,
+    /// 空闲链表中的块数
+    pub free_blocks: u64,
+    /// 最大的空闲块，更大的分配会失败
+    pub largest_free_block: u64,
+}
+
+impl LinkedListAllocator {
+    // 从空闲链表分配，slab缓存未命中时按类别大小分配
+    unsafe fn list_alloc(&self, layout: Layout) -> *mut u8 {
+        let (size, align) = block_layout(layout);
+
+        let alloc_start = self.with_free_list(|list| match list.allocate(size, align) {
+            Some(alloc_start) => Some(alloc_start),
+            None if self.grow(list, size, align) => list.allocate(size, align),
+            None => None,
+        });
+        match alloc_start {
+            Some(alloc_start) => {
+                self.record_alloc(size);
+                alloc_start as *mut u8
+            }
+            None => null_mut(),
+        }
+    }
+
+    // 把块还给空闲链表
+    unsafe fn list_dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = block_layout(layout);
+        self.with_free_list(|list| list.insert(ptr as usize, size));
+        self.record_free(size);
+    }
+
+    // 统计信息记录调用者拿到的内存：slab缓存命中和从链表分配都按块大小计入，
+    // 放进缓存的块不再算作已分配
+    fn record_alloc(&self, size: usize) {
+        let size = size as u64;
+        self.total_allocated.fetch_add(size, Ordering::Relaxed);
+        let current = self.current_allocated.fetch_add(size, Ordering::Relaxed) + size;
+        self.max_allocated.fetch_max(current, Ordering::Relaxed);
+        self.allocation_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_free(&self, size: usize) {
+        self.total_freed.fetch_add(size as u64, Ordering::Relaxed);
+        self.current_allocated.fetch_sub(size as u64, Ordering::Relaxed);
+        self.deallocation_count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for LinkedListAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let class = match slab::class_index(layout) {
+            Some(class) => class,
+            None => return self.list_alloc(layout),
+        };
+        if let Some(block) = with_lock(&self.slabs, |slabs| slabs.pop(class)) {
+            self.slab_hits[class].fetch_add(1, Ordering::Relaxed);
+            self.record_alloc(class_block_size(class));
+            return block;
+        }
+        self.slab_misses[class].fetch_add(1, Ordering::Relaxed);
+        self.list_alloc(slab::class_layout(class))
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        match slab::class_index(layout) {
+            Some(class) => {
+                if with_lock(&self.slabs, |slabs| slabs.push(class, ptr)) {
+                    self.record_free(class_block_size(class));
+                } else {
+                    self.list_dealloc(ptr, slab::class_layout(class));
+                }
+            }
+            None => self.list_dealloc(ptr, layout),
+        }
+    }
+}
+
+// 关中断后加锁，中断处理函数中的分配不会在锁上死锁
+fn with_lock<T, R>(mutex: &Mutex<T>, f: impl FnOnce(&mut T) -> R) -> R {
+    x86_64::instructions::interrupts::without_interrupts(|| f(&mut mutex.lock()))
+}
+
+fn align_up(addr: usize, align: usize) -> usize {
+    (addr + align - 1) & !(align - 1)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    // 测试用的内存区域，按64字节对齐以便测试对齐分配
+    #[repr(align(64))]
+    struct Arena([u8; 256]);
+
+    #[test_case]
+    fn insert_merges_with_both_neighbours() {
+        let mut arena = Arena([0; 256]);
+        let base = arena.0.as_mut_ptr() as usize;
+        let mut list = FreeList::new();
+        unsafe {
+            list.insert(base + 64, 32);
+            list.insert(base, 32);
+            // 两个块不相邻，按地址排序
+            assert_eq!(list.sizes().collect::<Vec<_>>(), [32, 32]);
+            assert_eq!(list.head as usize, base);
+
+            list.insert(base + 32, 32);
+        }
+        assert_eq!(list.sizes().collect::<Vec<_>>(), [96]);
+    }
+
+    #[test_case]
+    fn freed_blocks_coalesce_back_into_one() {
+        let mut arena = Arena([0; 256]);
+        let base = arena.0.as_mut_ptr() as usize;
+        let mut list = FreeList::new();
+        unsafe {
+            list.insert(base, 256);
+            let a = list.allocate(64, NODE_ALIGN).unwrap();
+            let b = list.allocate(64, NODE_ALIGN).unwrap();
+            let c = list.allocate(64, NODE_ALIGN).unwrap();
+            assert_eq!(list.sizes().collect::<Vec<_>>(), [64]);
+
+            list.insert(a, 64);
+            list.insert(c, 64);
+            assert_eq!(list.sizes().collect::<Vec<_>>(), [64, 128]);
+            list.insert(b, 64);
+        }
+        assert_eq!(list.sizes().collect::<Vec<_>>(), [256]);
+    }
+
+    #[test_case]
+    fn aligned_allocation_returns_the_gap_before_it() {
+        let mut arena = Arena([0; 256]);
+        let base = arena.0.as_mut_ptr() as usize;
+        let mut list = FreeList::new();
+        unsafe {
+            list.insert(base + 16, 240);
+            let block = list.allocate(16, 64).unwrap();
+            assert_eq!(block, base + 64);
+            assert_eq!(list.sizes().collect::<Vec<_>>(), [48, 176]);
+
+            list.insert(block, 16);
+        }
+        assert_eq!(list.sizes().collect::<Vec<_>>(), [240]);
+    }
+}
@@ -0,0 +1,89 @@
+// 小块分配的slab缓存
+// 8到2048字节的请求按大小类别分配，释放的块留在所属类别的缓存中，
+// 下次同类别的分配直接取出，不用遍历空闲链表
+
+use alloc::alloc::Layout;
+use core::ptr::null_mut;
+
+/// 各类别的块大小，块按自身大小对齐
+pub const SLAB_SIZES: [usize; 9] = [8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+
+// 每个类别最多缓存的字节数，超出后释放的块交还给空闲链表，避免堆被各类别瓜分
+const CACHE_LIMIT: usize = 4096;
+
+// 缓存中的空闲块，链表指针就放在块的开头
+struct FreeBlock {
+    next: *mut FreeBlock,
+}
+
+/// 各类别缓存的空闲块
+pub struct SlabCaches {
+    heads: [*mut FreeBlock; SLAB_SIZES.len()],
+    counts: [usize; SLAB_SIZES.len()],
+}
+
+// 缓存只通过LinkedListAllocator中的锁访问
+unsafe impl Send for SlabCaches {}
+
+impl SlabCaches {
+    pub const fn new() -> Self {
+        SlabCaches {
+            heads: [null_mut(); SLAB_SIZES.len()],
+            counts: [0; SLAB_SIZES.len()],
+        }
+    }
+
+    /// 取出一个缓存的块
+    pub fn pop(&mut self, class: usize) -> Option<*mut u8> {
+        let block = self.heads[class];
+        if block.is_null() {
+            return None;
+        }
+        self.heads[class] = unsafe { (*block).next };
+        self.counts[class] -= 1;
+        Some(block as *mut u8)
+    }
+
+    /// 缓存一个释放的块，缓存已满时返回false，由调用者交还给空闲链表
+    ///
+    /// 块必须按class_layout(class)分配并且不再被使用
+    pub unsafe fn push(&mut self, class: usize, ptr: *mut u8) -> bool {
+        if (self.counts[class] + 1) * SLAB_SIZES[class] > CACHE_LIMIT {
+            return false;
+        }
+        let block = ptr as *mut FreeBlock;
+        block.write(FreeBlock { next: self.heads[class] });
+        self.heads[class] = block;
+        self.counts[class] += 1;
+        true
+    }
+
+    /// 缓存的块数
+    pub fn cached(&self, class: usize) -> usize {
+        self.counts[class]
+    }
+}
+
+/// 能放下layout的最小类别，太大的请求返回None
+pub fn class_index(layout: Layout) -> Option<usize> {
+    let size = layout.size().max(layout.align());
+    SLAB_SIZES.iter().position(|&block_size| block_size >= size)
+}
+
+/// 类别的块向空闲链表申请内存时使用的Layout
+pub fn class_layout(class: usize) -> Layout {
+    let size = SLAB_SIZES[class];
+    Layout::from_size_align(size, size).unwrap()
+}
+
+/// 一个类别的统计
+#[derive(Debug, Clone, Copy)]
+pub struct SlabStats {
+    pub block_size: usize,
+    /// 由缓存满足的分配次数
+    pub hits: u64,
+    /// 缓存为空、从空闲链表分配的次数
+    pub misses: u64,
+    /// 当前缓存的块数
+    pub cached: usize,
+}
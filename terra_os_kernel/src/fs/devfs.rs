@@ -0,0 +1,148 @@
+// 设备文件系统
+// 挂载在/dev，提供null和zero两个字符设备，目录结构固定不可修改
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::new_fs::{FileSystem, FsError, Metadata};
+
+// 单次从/dev/zero读取的最大字节数
+const MAX_ZERO_READ: u64 = 64 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Device {
+    Null,
+    Zero,
+}
+
+// (名称, inode编号, 设备)，根目录的inode编号为0
+const DEVICES: [(&str, u64, Device); 2] = [
+    ("null", 1, Device::Null),
+    ("zero", 2, Device::Zero),
+];
+
+pub struct DevFs;
+
+impl DevFs {
+    pub const fn new() -> Self {
+        DevFs
+    }
+
+    fn find(path: &str) -> Result<Option<(u64, Device)>, FsError> {
+        let name = path.trim_matches('/');
+        if name.is_empty() {
+            return Ok(None);
+        }
+        DEVICES
+            .iter()
+            .find(|(device_name, _, _)| *device_name == name)
+            .map(|&(_, ino, device)| Some((ino, device)))
+            .ok_or(FsError::NotFound)
+    }
+
+    fn device(path: &str) -> Result<Device, FsError> {
+        Self::find(path)?.map(|(_, device)| device).ok_or(FsError::IsADirectory)
+    }
+
+    fn device_by_inode(inode_id: u64) -> Result<Device, FsError> {
+        if inode_id == 0 {
+            return Err(FsError::IsADirectory);
+        }
+        DEVICES
+            .iter()
+            .find(|(_, ino, _)| *ino == inode_id)
+            .map(|&(_, _, device)| device)
+            .ok_or(FsError::StaleHandle)
+    }
+
+    fn read_device(device: Device, length: u64) -> Vec<u8> {
+        match device {
+            Device::Null => Vec::new(),
+            Device::Zero => vec![0; length.min(MAX_ZERO_READ) as usize],
+        }
+    }
+}
+
+impl FileSystem for DevFs {
+    fn read(&self, path: &str, _offset: u64, length: u64) -> Result<Vec<u8>, FsError> {
+        Ok(Self::read_device(Self::device(path)?, length))
+    }
+
+    fn write(&self, path: &str, _offset: u64, data: &[u8]) -> Result<usize, FsError> {
+        // 写入的数据直接丢弃
+        Self::device(path).map(|_| data.len())
+    }
+
+    fn create(&self, _path: &str) -> Result<(), FsError> {
+        Err(FsError::NotPermitted)
+    }
+
+    fn copy_item(&self, _src: &str, _dst: &str, _recursive: bool) -> Result<(), FsError> {
+        Err(FsError::NotPermitted)
+    }
+
+    fn list_directory(&self, inode_id: u64) -> Result<Vec<(String, u64, bool, u64)>, FsError> {
+        if inode_id != 0 {
+            return Err(FsError::NotADirectory);
+        }
+        let mut entries = vec![(".".to_string(), 0, true, 0), ("..".to_string(), 0, true, 0)];
+        for (name, ino, _) in DEVICES.iter() {
+            entries.push((name.to_string(), *ino, false, 0));
+        }
+        Ok(entries)
+    }
+
+    fn create_directory(&self, _path: &str, _parent_inode_id: u64) -> Result<u64, FsError> {
+        Err(FsError::NotPermitted)
+    }
+
+    fn delete_item(&self, _path: &str, _recursive: bool) -> Result<(), FsError> {
+        Err(FsError::NotPermitted)
+    }
+
+    fn move_item(&self, _src: &str, _dst: &str) -> Result<(), FsError> {
+        Err(FsError::NotPermitted)
+    }
+
+    fn metadata(&self, path: &str) -> Result<Metadata, FsError> {
+        let (inode_id, is_dir) = match Self::find(path)? {
+            Some((ino, _)) => (ino, false),
+            None => (0, true),
+        };
+        Ok(Metadata {
+            inode_id,
+            is_dir,
+            size: 0,
+            blocks: 0,
+            generation: 0,
+        })
+    }
+
+    fn read_at(&self, inode_id: u64, _offset: u64, length: u64) -> Result<Vec<u8>, FsError> {
+        Ok(Self::read_device(Self::device_by_inode(inode_id)?, length))
+    }
+
+    fn write_at(&self, inode_id: u64, _offset: u64, data: &[u8]) -> Result<usize, FsError> {
+        Self::device_by_inode(inode_id).map(|_| data.len())
+    }
+
+    fn truncate(&self, inode_id: u64, _size: u64) -> Result<(), FsError> {
+        // 与Linux一致，截断字符设备不做任何事
+        Self::device_by_inode(inode_id).map(|_| ())
+    }
+
+    fn stat(&self, inode_id: u64) -> Result<Metadata, FsError> {
+        let is_dir = inode_id == 0;
+        if !is_dir {
+            Self::device_by_inode(inode_id)?;
+        }
+        Ok(Metadata {
+            inode_id,
+            is_dir,
+            size: 0,
+            blocks: 0,
+            generation: 0,
+        })
+    }
+}
@@ -0,0 +1,184 @@
+// 基于句柄的文件访问
+// open时解析一次路径并记住inode，之后的read/write/seek/stat都直接作用于inode
+
+use alloc::vec::Vec;
+use bitflags::bitflags;
+
+use super::new_fs::{FileSystem, FsError, Metadata};
+use super::{normalize_path, vfs};
+
+bitflags! {
+    /// 打开文件时的标志
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct OpenFlags: u32 {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        /// 文件不存在时创建
+        const CREATE = 1 << 2;
+        /// 打开时把文件截断为0字节（需要WRITE）
+        const TRUNCATE = 1 << 3;
+        /// 每次写入前把偏移移动到文件末尾（需要WRITE）
+        const APPEND = 1 << 4;
+    }
+}
+
+/// seek的起点
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    Start(u64),
+    End(i64),
+}
+
+/// 文件描述符
+pub type Fd = usize;
+
+/// 一个已打开的文件
+#[derive(Debug, Clone, Copy)]
+pub struct OpenFile {
+    // VFS inode编号
+    inode: u64,
+    // 打开时inode的代数，inode被删除并重新使用后句柄失效
+    generation: u32,
+    flags: OpenFlags,
+    offset: u64,
+}
+
+impl OpenFile {
+    /// 按flags打开path
+    pub fn open(path: &str, flags: OpenFlags) -> Result<Self, FsError> {
+        if !flags.intersects(OpenFlags::READ | OpenFlags::WRITE) {
+            return Err(FsError::InvalidArgument);
+        }
+        if flags.intersects(OpenFlags::TRUNCATE | OpenFlags::APPEND) && !flags.contains(OpenFlags::WRITE) {
+            return Err(FsError::InvalidArgument);
+        }
+
+        let vfs = vfs()?;
+        let path = normalize_path(path);
+        let meta = match vfs.metadata(&path) {
+            Ok(meta) => meta,
+            Err(FsError::NotFound) if flags.contains(OpenFlags::CREATE) => {
+                vfs.create(&path)?;
+                vfs.metadata(&path)?
+            }
+            Err(e) => return Err(e),
+        };
+
+        if meta.is_dir && flags.contains(OpenFlags::WRITE) {
+            return Err(FsError::IsADirectory);
+        }
+        if flags.contains(OpenFlags::TRUNCATE) {
+            vfs.truncate(meta.inode_id, 0)?;
+        }
+
+        Ok(Self {
+            inode: meta.inode_id,
+            generation: meta.generation,
+            flags,
+            offset: 0,
+        })
+    }
+
+    /// 从当前偏移读取最多length字节，并前移偏移
+    pub fn read(&mut self, length: u64) -> Result<Vec<u8>, FsError> {
+        if !self.flags.contains(OpenFlags::READ) {
+            return Err(FsError::BadFileDescriptor);
+        }
+        self.stat()?;
+        let data = vfs()?.read_at(self.inode, self.offset, length)?;
+        self.offset += data.len() as u64;
+        Ok(data)
+    }
+
+    /// 在当前偏移写入数据，并前移偏移
+    pub fn write(&mut self, data: &[u8]) -> Result<usize, FsError> {
+        if !self.flags.contains(OpenFlags::WRITE) {
+            return Err(FsError::BadFileDescriptor);
+        }
+        let size = self.stat()?.size;
+        if self.flags.contains(OpenFlags::APPEND) {
+            self.offset = size;
+        }
+        let written = vfs()?.write_at(self.inode, self.offset, data)?;
+        self.offset += written as u64;
+        Ok(written)
+    }
+
+    /// 移动偏移，返回新的偏移；允许移动到文件末尾之后
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<u64, FsError> {
+        let (base, delta) = match pos {
+            SeekFrom::Start(offset) => (offset, 0),
+            SeekFrom::End(delta) => (self.stat()?.size, delta),
+        };
+        let offset = if delta >= 0 {
+            base.checked_add(delta as u64)
+        } else {
+            base.checked_sub(delta.unsigned_abs())
+        };
+        self.offset = offset.ok_or(FsError::InvalidArgument)?;
+        Ok(self.offset)
+    }
+
+    /// 文件的元数据；文件已被删除时返回StaleHandle，即使inode已分配给了新文件
+    pub fn stat(&self) -> Result<Metadata, FsError> {
+        match vfs()?.stat(self.inode) {
+            Ok(meta) if meta.generation == self.generation => Ok(meta),
+            Ok(_) => Err(FsError::StaleHandle),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// 打开文件表，每个终端会话一张
+pub struct FileTable {
+    files: Vec<Option<OpenFile>>,
+}
+
+impl FileTable {
+    pub const fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    /// 打开文件并返回最小的可用文件描述符
+    pub fn open(&mut self, path: &str, flags: OpenFlags) -> Result<Fd, FsError> {
+        let file = OpenFile::open(path, flags)?;
+        match self.files.iter().position(|slot| slot.is_none()) {
+            Some(fd) => {
+                self.files[fd] = Some(file);
+                Ok(fd)
+            }
+            None => {
+                self.files.push(Some(file));
+                Ok(self.files.len() - 1)
+            }
+        }
+    }
+
+    pub fn get(&mut self, fd: Fd) -> Result<&mut OpenFile, FsError> {
+        self.files
+            .get_mut(fd)
+            .and_then(|slot| slot.as_mut())
+            .ok_or(FsError::BadFileDescriptor)
+    }
+
+    pub fn read(&mut self, fd: Fd, length: u64) -> Result<Vec<u8>, FsError> {
+        self.get(fd)?.read(length)
+    }
+
+    pub fn write(&mut self, fd: Fd, data: &[u8]) -> Result<usize, FsError> {
+        self.get(fd)?.write(data)
+    }
+
+    pub fn seek(&mut self, fd: Fd, pos: SeekFrom) -> Result<u64, FsError> {
+        self.get(fd)?.seek(pos)
+    }
+
+    pub fn stat(&mut self, fd: Fd) -> Result<Metadata, FsError> {
+        self.get(fd)?.stat()
+    }
+
+    pub fn close(&mut self, fd: Fd) -> Result<(), FsError> {
+        let slot = self.files.get_mut(fd).ok_or(FsError::BadFileDescriptor)?;
+        slot.take().map(|_| ()).ok_or(FsError::BadFileDescriptor)
+    }
+}
@@ -1,7 +1,364 @@
 // 文件系统模块
-// 这个文件是fs模块的入口点
+// 这个文件是fs模块的入口点，实现了虚拟文件系统(VFS)层
+//
+// VFS维护一张挂载表，把绝对路径按最长前缀匹配分派到对应的文件系统，
+// 各文件系统只需实现new_fs::FileSystem接口即可挂载
 
 // 导出new_fs模块
 pub mod new_fs;
+pub mod devfs;
+pub mod procfs;
+pub mod file;
 
-// 这里可以添加文件系统相关的公共API、结构体和函数
\ No newline at end of file
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU16, Ordering};
+use spin::{Once, RwLock};
+
+use self::new_fs::{FileSystem, MemoryBlockDevice, Metadata, SimpleFileSystem};
+
+pub use self::new_fs::FsError;
+
+/// 可挂载的文件系统对象
+pub type FsRef = Arc<dyn FileSystem + Send + Sync>;
+
+/// VFS中的inode，由挂载点编号和文件系统内部的inode编号组成
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VfsInode {
+    pub mount_id: u16,
+    pub ino: u64,
+}
+
+impl VfsInode {
+    const INO_BITS: u32 = 48;
+    const INO_MASK: u64 = (1 << Self::INO_BITS) - 1;
+
+    /// 编码为FileSystem接口使用的u64 inode编号
+    pub fn to_id(self) -> u64 {
+        ((self.mount_id as u64) << Self::INO_BITS) | (self.ino & Self::INO_MASK)
+    }
+
+    pub fn from_id(id: u64) -> Self {
+        Self {
+            mount_id: (id >> Self::INO_BITS) as u16,
+            ino: id & Self::INO_MASK,
+        }
+    }
+}
+
+/// VFS目录项
+#[derive(Debug, Clone)]
+pub struct Dentry {
+    pub name: String,
+    pub inode: VfsInode,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+// 挂载表中的一项
+struct Mount {
+    id: u16,
+    path: String,
+    fs_type: &'static str,
+    fs: FsRef,
+}
+
+/// 虚拟文件系统
+pub struct Vfs {
+    mounts: RwLock<Vec<Mount>>,
+    next_id: AtomicU16,
+}
+
+impl Vfs {
+    pub const fn new() -> Self {
+        Self {
+            mounts: RwLock::new(Vec::new()),
+            next_id: AtomicU16::new(0),
+        }
+    }
+
+    /// 把文件系统挂载到path，除根目录外挂载点必须是已存在的目录
+    pub fn mount(&self, path: &str, fs_type: &'static str, fs: FsRef) -> Result<(), FsError> {
+        let path = normalize_path(path);
+        if self.mounts.read().iter().any(|m| m.path == path) {
+            return Err(FsError::Busy);
+        }
+        if path != "/" && !self.metadata(&path)?.is_dir {
+            return Err(FsError::NotADirectory);
+        }
+
+        self.mounts.write().push(Mount {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            path,
+            fs_type,
+            fs,
+        });
+        Ok(())
+    }
+
+    /// 卸载path上的文件系统，其下还有挂载点时拒绝卸载
+    pub fn unmount(&self, path: &str) -> Result<(), FsError> {
+        let path = normalize_path(path);
+        let mut mounts = self.mounts.write();
+        let index = mounts.iter().position(|m| m.path == path).ok_or(FsError::InvalidArgument)?;
+        if mounts.iter().any(|m| m.path != path && is_under(&m.path, &path)) {
+            return Err(FsError::Busy);
+        }
+        mounts.remove(index);
+        Ok(())
+    }
+
+    /// 列出挂载表：(挂载点, 文件系统类型)
+    pub fn mounts(&self) -> Vec<(String, &'static str)> {
+        self.mounts.read().iter().map(|m| (m.path.clone(), m.fs_type)).collect()
+    }
+
+    /// 把路径解析到挂载点：返回挂载点编号、文件系统和文件系统内的路径
+    fn route(&self, path: &str) -> Result<(u16, FsRef, String), FsError> {
+        let path = normalize_path(path);
+        let mounts = self.mounts.read();
+        let mount = mounts
+            .iter()
+            .filter(|m| is_under(&path, &m.path))
+            .max_by_key(|m| m.path.len())
+            .ok_or(FsError::NotMounted)?;
+
+        let rest = if mount.path == "/" { &path[..] } else { &path[mount.path.len()..] };
+        let inner = if rest.is_empty() { "/".to_string() } else { rest.to_string() };
+        Ok((mount.id, mount.fs.clone(), inner))
+    }
+
+    fn mount_by_id(&self, id: u16) -> Result<FsRef, FsError> {
+        self.mounts
+            .read()
+            .iter()
+            .find(|m| m.id == id)
+            .map(|m| m.fs.clone())
+            .ok_or(FsError::StaleHandle)
+    }
+
+    /// 按路径列出目录
+    pub fn read_dir(&self, path: &str) -> Result<Vec<Dentry>, FsError> {
+        let inode = self.lookup(path)?;
+        let fs = self.mount_by_id(inode.mount_id)?;
+        Ok(fs
+            .list_directory(inode.ino)?
+            .into_iter()
+            .map(|(name, ino, is_dir, size)| Dentry {
+                name,
+                inode: VfsInode { mount_id: inode.mount_id, ino },
+                is_dir,
+                size,
+            })
+            .collect())
+    }
+
+    /// 把路径解析为VFS inode
+    pub fn lookup(&self, path: &str) -> Result<VfsInode, FsError> {
+        let (mount_id, fs, inner) = self.route(path)?;
+        Ok(VfsInode {
+            mount_id,
+            ino: fs.metadata(&inner)?.inode_id,
+        })
+    }
+
+    /// mv/cp的目标为已有目录时，放入该目录下同名项
+    fn target_path(&self, src: &str, dst: &str) -> String {
+        let src = normalize_path(src);
+        let dst = normalize_path(dst);
+        match self.metadata(&dst) {
+            Ok(meta) if meta.is_dir => {
+                let name = src.rsplit('/').next().unwrap_or("");
+                join_path(&dst, name)
+            }
+            _ => dst,
+        }
+    }
+}
+
+impl FileSystem for Vfs {
+    fn read(&self, path: &str, offset: u64, length: u64) -> Result<Vec<u8>, FsError> {
+        let (_, fs, inner) = self.route(path)?;
+        fs.read(&inner, offset, length)
+    }
+
+    fn write(&self, path: &str, offset: u64, data: &[u8]) -> Result<usize, FsError> {
+        let (_, fs, inner) = self.route(path)?;
+        fs.write(&inner, offset, data)
+    }
+
+    fn create(&self, path: &str) -> Result<(), FsError> {
+        let (_, fs, inner) = self.route(path)?;
+        fs.create(&inner)
+    }
+
+    fn copy_item(&self, src: &str, dst: &str, recursive: bool) -> Result<(), FsError> {
+        let (src_mount, src_fs, src_inner) = self.route(src)?;
+        let (dst_mount, _, dst_inner) = self.route(dst)?;
+        if src_mount == dst_mount {
+            return src_fs.copy_item(&src_inner, &dst_inner, recursive);
+        }
+
+        // 跨文件系统复制：只支持普通文件，通过读出再写入完成
+        let meta = src_fs.metadata(&src_inner)?;
+        if meta.is_dir {
+            return Err(FsError::Unsupported);
+        }
+        let data = src_fs.read(&src_inner, 0, meta.size)?;
+        let (_, dst_fs, dst_inner) = self.route(&self.target_path(src, dst))?;
+        dst_fs.create(&dst_inner)?;
+        dst_fs.write(&dst_inner, 0, &data).map(|_| ())
+    }
+
+    fn list_directory(&self, inode_id: u64) -> Result<Vec<(String, u64, bool, u64)>, FsError> {
+        let inode = VfsInode::from_id(inode_id);
+        let fs = self.mount_by_id(inode.mount_id)?;
+        Ok(fs
+            .list_directory(inode.ino)?
+            .into_iter()
+            .map(|(name, ino, is_dir, size)| {
+                let id = VfsInode { mount_id: inode.mount_id, ino }.to_id();
+                (name, id, is_dir, size)
+            })
+            .collect())
+    }
+
+    fn create_directory(&self, path: &str, parent_inode_id: u64) -> Result<u64, FsError> {
+        // 相对路径只能在父目录所在的文件系统内解析
+        let (mount_id, fs, inner, parent) = if path.starts_with('/') {
+            let (mount_id, fs, inner) = self.route(path)?;
+            (mount_id, fs, inner, 0)
+        } else {
+            let parent = VfsInode::from_id(parent_inode_id);
+            let fs = self.mount_by_id(parent.mount_id)?;
+            (parent.mount_id, fs, path.to_string(), parent.ino)
+        };
+        let ino = fs.create_directory(&inner, parent)?;
+        Ok(VfsInode { mount_id, ino }.to_id())
+    }
+
+    fn delete_item(&self, path: &str, recursive: bool) -> Result<(), FsError> {
+        let path = normalize_path(path);
+        if self.mounts.read().iter().any(|m| m.path == path) {
+            return Err(FsError::Busy);
+        }
+        let (_, fs, inner) = self.route(&path)?;
+        fs.delete_item(&inner, recursive)
+    }
+
+    fn move_item(&self, src: &str, dst: &str) -> Result<(), FsError> {
+        let (src_mount, src_fs, src_inner) = self.route(src)?;
+        let (dst_mount, _, dst_inner) = self.route(dst)?;
+        if src_mount != dst_mount {
+            return Err(FsError::CrossDevice);
+        }
+        src_fs.move_item(&src_inner, &dst_inner)
+    }
+
+    fn metadata(&self, path: &str) -> Result<Metadata, FsError> {
+        let (mount_id, fs, inner) = self.route(path)?;
+        let mut meta = fs.metadata(&inner)?;
+        meta.inode_id = VfsInode { mount_id, ino: meta.inode_id }.to_id();
+        Ok(meta)
+    }
+
+    fn read_at(&self, inode_id: u64, offset: u64, length: u64) -> Result<Vec<u8>, FsError> {
+        let inode = VfsInode::from_id(inode_id);
+        self.mount_by_id(inode.mount_id)?.read_at(inode.ino, offset, length)
+    }
+
+    fn write_at(&self, inode_id: u64, offset: u64, data: &[u8]) -> Result<usize, FsError> {
+        let inode = VfsInode::from_id(inode_id);
+        self.mount_by_id(inode.mount_id)?.write_at(inode.ino, offset, data)
+    }
+
+    fn truncate(&self, inode_id: u64, size: u64) -> Result<(), FsError> {
+        let inode = VfsInode::from_id(inode_id);
+        self.mount_by_id(inode.mount_id)?.truncate(inode.ino, size)
+    }
+
+    fn stat(&self, inode_id: u64) -> Result<Metadata, FsError> {
+        let inode = VfsInode::from_id(inode_id);
+        let mut meta = self.mount_by_id(inode.mount_id)?.stat(inode.ino)?;
+        meta.inode_id = inode_id;
+        Ok(meta)
+    }
+}
+
+/// 规范化路径：处理`.`、`..`和重复的`/`，结果总是以`/`开头的绝对路径
+pub fn normalize_path(path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            name => components.push(name),
+        }
+    }
+
+    let mut normalized = String::new();
+    for component in components {
+        normalized.push('/');
+        normalized.push_str(component);
+    }
+    if normalized.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+/// 把path相对于cwd解析为规范化的绝对路径，path为绝对路径时忽略cwd
+pub fn resolve_path(cwd: &str, path: &str) -> String {
+    if path.starts_with('/') {
+        normalize_path(path)
+    } else {
+        normalize_path(&join_path(cwd, path))
+    }
+}
+
+/// 拼接目录和名称
+pub fn join_path(dir: &str, name: &str) -> String {
+    if dir.ends_with('/') {
+        alloc::format!("{}{}", dir, name)
+    } else {
+        alloc::format!("{}/{}", dir, name)
+    }
+}
+
+// 判断规范化路径path是否位于mount_path之下（含自身）
+fn is_under(path: &str, mount_path: &str) -> bool {
+    mount_path == "/"
+        || path == mount_path
+        || (path.starts_with(mount_path) && path.as_bytes().get(mount_path.len()) == Some(&b'/'))
+}
+
+// 全局VFS，启动时挂载一次，之后由所有命令共享
+// 各文件系统内部自行加锁，因此可以通过&'static引用并发访问
+static VFS: Once<Vfs> = Once::new();
+
+/// 创建VFS并挂载根文件系统、/dev和/proc，应在启动时调用一次
+pub fn init(allocator: &'static crate::allocator::LinkedListAllocator) -> Result<(), FsError> {
+    let vfs = VFS.call_once(Vfs::new);
+
+    let mut root = SimpleFileSystem::new(MemoryBlockDevice::new());
+    root.init()?;
+    for dir in ["dev", "proc"] {
+        match root.create_directory(dir, 0) {
+            Ok(_) | Err(FsError::AlreadyExists) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    vfs.mount("/", "simplefs", Arc::new(root))?;
+    vfs.mount("/dev", "devfs", Arc::new(devfs::DevFs::new()))?;
+    vfs.mount("/proc", "procfs", Arc::new(procfs::ProcFs::new(allocator)))?;
+    Ok(())
+}
+
+/// 获取全局VFS
+pub fn vfs() -> Result<&'static Vfs, FsError> {
+    VFS.r#try().ok_or(FsError::NotMounted)
+}
@@ -0,0 +1,93 @@
+// 块设备抽象
+// 文件系统只通过BlockDevice访问存储，RAM盘、ATA磁盘、virtio磁盘等都可以作为后端
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::FsError;
+
+/// 按块读写的存储设备
+pub trait BlockDevice {
+    /// 设备块大小（字节）
+    fn block_size(&self) -> usize;
+
+    /// 设备总块数
+    fn block_count(&self) -> u64;
+
+    /// 从start块开始读取，buf长度必须是块大小的整数倍
+    fn read_blocks(&mut self, start: u64, buf: &mut [u8]) -> Result<(), FsError>;
+
+    /// 从start块开始写入，buf长度必须是块大小的整数倍
+    fn write_blocks(&mut self, start: u64, buf: &[u8]) -> Result<(), FsError>;
+
+    /// 将缓存的写入落盘
+    fn flush(&mut self) -> Result<(), FsError>;
+}
+
+// 内存块设备实现
+pub struct MemoryBlockDevice {
+    data: Vec<u8>,
+    size: u64,
+}
+
+impl MemoryBlockDevice {
+    /// 内存块设备的块大小
+    pub const BLOCK_SIZE: usize = 512;
+
+    pub fn new() -> Self {
+        // 默认初始化为1MB的内存块
+        Self::with_size(1024 * 1024)
+    }
+
+    /// 创建指定字节数的内存块设备，大小向上取整到块大小
+    pub fn with_size(size: usize) -> Self {
+        let blocks = size.div_ceil(Self::BLOCK_SIZE);
+        let size = blocks * Self::BLOCK_SIZE;
+        Self {
+            data: vec![0; size],
+            size: size as u64,
+        }
+    }
+
+    fn range(&self, start: u64, len: usize) -> Result<core::ops::Range<usize>, FsError> {
+        if !len.is_multiple_of(Self::BLOCK_SIZE) {
+            return Err(FsError::InvalidArgument);
+        }
+        let begin = usize::try_from(start)
+            .ok()
+            .and_then(|start| start.checked_mul(Self::BLOCK_SIZE))
+            .ok_or(FsError::InvalidArgument)?;
+        let end = begin.checked_add(len).ok_or(FsError::InvalidArgument)?;
+        if end as u64 > self.size {
+            return Err(FsError::Io);
+        }
+        Ok(begin..end)
+    }
+}
+
+impl BlockDevice for MemoryBlockDevice {
+    fn block_size(&self) -> usize {
+        Self::BLOCK_SIZE
+    }
+
+    fn block_count(&self) -> u64 {
+        self.size / Self::BLOCK_SIZE as u64
+    }
+
+    fn read_blocks(&mut self, start: u64, buf: &mut [u8]) -> Result<(), FsError> {
+        let range = self.range(start, buf.len())?;
+        buf.copy_from_slice(&self.data[range]);
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, start: u64, buf: &[u8]) -> Result<(), FsError> {
+        let range = self.range(start, buf.len())?;
+        self.data[range].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), FsError> {
+        // 内存设备没有需要落盘的缓存
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+// 文件系统错误类型
+// 每个变体对应一个POSIX errno（见变体注释），终端通过Display输出统一的错误信息
+
+use core::fmt;
+
+/// 文件系统操作的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    /// EPERM：操作不被允许
+    NotPermitted,
+    /// ENOENT：文件或目录不存在
+    NotFound,
+    /// EIO：设备读写失败
+    Io,
+    /// EBADF：文件描述符无效或打开方式不允许该操作
+    BadFileDescriptor,
+    /// EBUSY：挂载点正在使用
+    Busy,
+    /// EEXIST：文件已存在
+    AlreadyExists,
+    /// EXDEV：不能跨文件系统操作
+    CrossDevice,
+    /// ENODEV：文件系统未挂载或未初始化
+    NotMounted,
+    /// ENOTDIR：路径中的某一项不是目录
+    NotADirectory,
+    /// EISDIR：对目录执行了只适用于文件的操作
+    IsADirectory,
+    /// EINVAL：参数无效
+    InvalidArgument,
+    /// EFBIG：文件超过最大长度
+    FileTooLarge,
+    /// ENOSPC：没有空闲的块或inode
+    NoSpace,
+    /// EROFS：只读文件系统
+    ReadOnly,
+    /// ENAMETOOLONG：文件名过长
+    NameTooLong,
+    /// ENOTEMPTY：目录非空
+    DirectoryNotEmpty,
+    /// EOPNOTSUPP：文件系统不支持该操作
+    Unsupported,
+    /// ESTALE：inode已被释放
+    StaleHandle,
+    /// EUCLEAN：磁盘上的结构已损坏
+    Corrupted,
+}
+
+impl FsError {
+    /// 错误描述
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FsError::NotPermitted => "Operation not permitted",
+            FsError::NotFound => "No such file or directory",
+            FsError::Io => "Input/output error",
+            FsError::BadFileDescriptor => "Bad file descriptor",
+            FsError::Busy => "Device or resource busy",
+            FsError::AlreadyExists => "File exists",
+            FsError::CrossDevice => "Cross-device link",
+            FsError::NotMounted => "Filesystem not mounted",
+            FsError::NotADirectory => "Not a directory",
+            FsError::IsADirectory => "Is a directory",
+            FsError::InvalidArgument => "Invalid argument",
+            FsError::FileTooLarge => "File too large",
+            FsError::NoSpace => "No space left on device",
+            FsError::ReadOnly => "Read-only file system",
+            FsError::NameTooLong => "File name too long",
+            FsError::DirectoryNotEmpty => "Directory not empty",
+            FsError::Unsupported => "Operation not supported",
+            FsError::StaleHandle => "Stale file handle",
+            FsError::Corrupted => "Structure needs cleaning",
+        }
+    }
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
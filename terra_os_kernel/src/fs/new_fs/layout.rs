@@ -0,0 +1,267 @@
+// 磁盘布局定义
+// 块0为超级块，随后依次为块位图、inode表和数据区
+//
+// +-------------+-------------+-------------+----------------------+
+// | 超级块 (0)  | 块位图      | inode表     | 数据块               |
+// +-------------+-------------+-------------+----------------------+
+
+/// 块大小（字节）
+pub const BLOCK_SIZE: usize = 512;
+
+/// 文件系统魔数 "TRFS"
+pub const MAGIC: u32 = 0x5452_4653;
+
+/// 根目录的inode编号
+pub const ROOT_INODE: u64 = 0;
+
+/// 每个inode在磁盘上占用的字节数
+pub const INODE_SIZE: usize = 64;
+pub const INODES_PER_BLOCK: usize = BLOCK_SIZE / INODE_SIZE;
+
+/// inode中直接块指针的数量，另有一个一级间接块
+pub const DIRECT_BLOCKS: usize = 11;
+pub const POINTERS_PER_BLOCK: usize = BLOCK_SIZE / 4;
+
+/// 单个文件的最大字节数
+pub const MAX_FILE_SIZE: u64 = ((DIRECT_BLOCKS + POINTERS_PER_BLOCK) * BLOCK_SIZE) as u64;
+
+/// 每个目录项在磁盘上占用的字节数
+pub const DIR_ENTRY_SIZE: usize = 64;
+
+/// 目录项中文件名的最大字节数
+pub const MAX_NAME_LEN: usize = DIR_ENTRY_SIZE - 8;
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn write_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+pub fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+pub fn write_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+fn write_u64(buf: &mut [u8], offset: usize, value: u64) {
+    buf[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+/// 超级块，记录文件系统的几何信息和空闲计数
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Superblock {
+    pub total_blocks: u32,
+    pub inode_count: u32,
+    pub bitmap_start: u32,
+    pub bitmap_blocks: u32,
+    pub inode_table_start: u32,
+    pub inode_table_blocks: u32,
+    pub data_start: u32,
+    pub free_blocks: u32,
+    pub free_inodes: u32,
+}
+
+impl Superblock {
+    /// 根据设备的总块数计算布局
+    pub fn for_device(total_blocks: u32) -> Option<Self> {
+        let bits_per_block = (BLOCK_SIZE * 8) as u32;
+        let bitmap_blocks = total_blocks.div_ceil(bits_per_block);
+        // 每8个块分配一个inode，至少保留16个
+        let inode_count = (total_blocks / 8).max(16);
+        let inode_table_blocks = inode_count.div_ceil(INODES_PER_BLOCK as u32);
+        let bitmap_start = 1;
+        let inode_table_start = bitmap_start + bitmap_blocks;
+        let data_start = inode_table_start + inode_table_blocks;
+
+        if data_start >= total_blocks {
+            return None;
+        }
+
+        Some(Self {
+            total_blocks,
+            inode_count,
+            bitmap_start,
+            bitmap_blocks,
+            inode_table_start,
+            inode_table_blocks,
+            data_start,
+            free_blocks: total_blocks - data_start,
+            free_inodes: inode_count,
+        })
+    }
+
+    /// 从块0的内容解析超级块，魔数不匹配时返回None
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if read_u32(buf, 0) != MAGIC || read_u32(buf, 4) as usize != BLOCK_SIZE {
+            return None;
+        }
+        Some(Self {
+            total_blocks: read_u32(buf, 8),
+            inode_count: read_u32(buf, 12),
+            bitmap_start: read_u32(buf, 16),
+            bitmap_blocks: read_u32(buf, 20),
+            inode_table_start: read_u32(buf, 24),
+            inode_table_blocks: read_u32(buf, 28),
+            data_start: read_u32(buf, 32),
+            free_blocks: read_u32(buf, 36),
+            free_inodes: read_u32(buf, 40),
+        })
+    }
+
+    pub fn encode(&self, buf: &mut [u8]) {
+        write_u32(buf, 0, MAGIC);
+        write_u32(buf, 4, BLOCK_SIZE as u32);
+        write_u32(buf, 8, self.total_blocks);
+        write_u32(buf, 12, self.inode_count);
+        write_u32(buf, 16, self.bitmap_start);
+        write_u32(buf, 20, self.bitmap_blocks);
+        write_u32(buf, 24, self.inode_table_start);
+        write_u32(buf, 28, self.inode_table_blocks);
+        write_u32(buf, 32, self.data_start);
+        write_u32(buf, 36, self.free_blocks);
+        write_u32(buf, 40, self.free_inodes);
+    }
+}
+
+/// inode类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InodeKind {
+    Free = 0,
+    File = 1,
+    Directory = 2,
+}
+
+impl InodeKind {
+    fn from_u16(value: u16) -> Self {
+        match value {
+            1 => InodeKind::File,
+            2 => InodeKind::Directory,
+            _ => InodeKind::Free,
+        }
+    }
+}
+
+/// 磁盘上的inode
+#[derive(Debug, Clone, Copy)]
+pub struct Inode {
+    pub kind: InodeKind,
+    pub links: u16,
+    /// 每次释放时加一，打开的句柄据此发现inode已被删除并重新使用
+    pub generation: u32,
+    pub size: u64,
+    pub direct: [u32; DIRECT_BLOCKS],
+    pub indirect: u32,
+}
+
+impl Inode {
+    pub const fn empty() -> Self {
+        Self {
+            kind: InodeKind::Free,
+            links: 0,
+            generation: 0,
+            size: 0,
+            direct: [0; DIRECT_BLOCKS],
+            indirect: 0,
+        }
+    }
+
+    pub fn new(kind: InodeKind) -> Self {
+        Self {
+            kind,
+            links: 1,
+            ..Self::empty()
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == InodeKind::Directory
+    }
+
+    pub fn decode(buf: &[u8]) -> Self {
+        let mut direct = [0u32; DIRECT_BLOCKS];
+        for (i, block) in direct.iter_mut().enumerate() {
+            *block = read_u32(buf, 16 + i * 4);
+        }
+        Self {
+            kind: InodeKind::from_u16(read_u16(buf, 0)),
+            links: read_u16(buf, 2),
+            generation: read_u32(buf, 4),
+            size: read_u64(buf, 8),
+            direct,
+            indirect: read_u32(buf, 16 + DIRECT_BLOCKS * 4),
+        }
+    }
+
+    pub fn encode(&self, buf: &mut [u8]) {
+        write_u16(buf, 0, self.kind as u16);
+        write_u16(buf, 2, self.links);
+        write_u32(buf, 4, self.generation);
+        write_u64(buf, 8, self.size);
+        for (i, block) in self.direct.iter().enumerate() {
+            write_u32(buf, 16 + i * 4, *block);
+        }
+        write_u32(buf, 16 + DIRECT_BLOCKS * 4, self.indirect);
+    }
+}
+
+/// 磁盘上的目录项，name_len为0表示空槽
+#[derive(Debug, Clone, Copy)]
+pub struct DirEntry {
+    pub inode: u32,
+    pub is_dir: bool,
+    name_len: u8,
+    name: [u8; MAX_NAME_LEN],
+}
+
+impl DirEntry {
+    pub fn new(name: &str, inode: u64, is_dir: bool) -> Self {
+        let mut buf = [0u8; MAX_NAME_LEN];
+        let len = name.len().min(MAX_NAME_LEN);
+        buf[..len].copy_from_slice(&name.as_bytes()[..len]);
+        Self {
+            inode: inode as u32,
+            is_dir,
+            name_len: len as u8,
+            name: buf,
+        }
+    }
+
+    pub fn is_used(&self) -> bool {
+        self.name_len != 0
+    }
+
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len as usize]).unwrap_or("?")
+    }
+
+    pub fn decode(buf: &[u8]) -> Self {
+        let mut name = [0u8; MAX_NAME_LEN];
+        name.copy_from_slice(&buf[8..8 + MAX_NAME_LEN]);
+        Self {
+            inode: read_u32(buf, 0),
+            name_len: buf[4].min(MAX_NAME_LEN as u8),
+            is_dir: buf[5] != 0,
+            name,
+        }
+    }
+
+    pub fn encode(&self, buf: &mut [u8]) {
+        write_u32(buf, 0, self.inode);
+        buf[4] = self.name_len;
+        buf[5] = self.is_dir as u8;
+        write_u16(buf, 6, 0);
+        buf[8..8 + MAX_NAME_LEN].copy_from_slice(&self.name);
+    }
+}
@@ -1,112 +1,954 @@
 // 新文件系统实现
 // 这个文件是new_fs模块的入口点
 
+mod device;
+mod error;
+mod layout;
+
 // 导入必要的类型
 use alloc::string::String;
 use alloc::string::ToString;
-use alloc::vec;
 use alloc::vec::Vec;
+use spin::Mutex;
+
+pub use self::device::{BlockDevice, MemoryBlockDevice};
+pub use self::error::FsError;
 
-// 这里可以添加文件系统相关的结构体、函数和实现
+use self::layout::{
+    DirEntry, Inode, InodeKind, Superblock, BLOCK_SIZE, DIRECT_BLOCKS, DIR_ENTRY_SIZE,
+    INODES_PER_BLOCK, INODE_SIZE, MAX_FILE_SIZE, MAX_NAME_LEN, POINTERS_PER_BLOCK, ROOT_INODE,
+};
+
+/// 文件或目录的元数据
+#[derive(Debug, Clone, Copy)]
+pub struct Metadata {
+    pub inode_id: u64,
+    pub is_dir: bool,
+    pub size: u64,
+    pub blocks: u64,
+    /// inode的代数，inode被删除后重新分配时改变
+    pub generation: u32,
+}
 
-// 示例：简单的文件系统接口
+// 简单的文件系统接口
 pub trait FileSystem {
-    fn read(&self, path: &str, offset: u64, length: u64) -> Result<Vec<u8>, &'static str>;
-    fn write(&self, path: &str, offset: u64, data: &[u8]) -> Result<usize, &'static str>;
-    fn create(&self, path: &str) -> Result<(), &'static str>;
-    fn delete(&self, path: &str) -> Result<(), &'static str>;
-    fn copy_item(&self, src: &str, dst: &str, recursive: bool) -> Result<(), &'static str>;
-    fn list_directory(&self, inode_id: u64) -> Result<Vec<(String, u64, bool, u64)>, &'static str>;
-    fn create_directory(&self, path: &str, parent_inode_id: u64) -> Result<u64, &'static str>;
-    fn delete_item(&self, path: &str, recursive: bool) -> Result<(), &'static str>;
-    fn move_item(&self, src: &str, dst: &str) -> Result<(), &'static str>;
+    fn read(&self, path: &str, offset: u64, length: u64) -> Result<Vec<u8>, FsError>;
+    fn write(&self, path: &str, offset: u64, data: &[u8]) -> Result<usize, FsError>;
+    fn create(&self, path: &str) -> Result<(), FsError>;
+    fn copy_item(&self, src: &str, dst: &str, recursive: bool) -> Result<(), FsError>;
+    fn list_directory(&self, inode_id: u64) -> Result<Vec<(String, u64, bool, u64)>, FsError>;
+    fn create_directory(&self, path: &str, parent_inode_id: u64) -> Result<u64, FsError>;
+    fn delete_item(&self, path: &str, recursive: bool) -> Result<(), FsError>;
+    fn move_item(&self, src: &str, dst: &str) -> Result<(), FsError>;
+    fn metadata(&self, path: &str) -> Result<Metadata, FsError>;
+
+    // 基于inode编号的访问，供已打开的文件句柄使用，避免每次重新解析路径
+    fn read_at(&self, inode_id: u64, offset: u64, length: u64) -> Result<Vec<u8>, FsError>;
+    fn write_at(&self, inode_id: u64, offset: u64, data: &[u8]) -> Result<usize, FsError>;
+    fn truncate(&self, inode_id: u64, size: u64) -> Result<(), FsError>;
+    fn stat(&self, inode_id: u64) -> Result<Metadata, FsError>;
+}
+
+// 简单文件系统实现，可以建立在任意块设备之上
+pub struct SimpleFileSystem<D: BlockDevice = MemoryBlockDevice> {
+    inner: Mutex<FsInner<D>>,
+    mounted: bool,
 }
 
-// 内存块设备实现
-pub struct MemoryBlockDevice {
-    data: Vec<u8>,
-    size: u64,
+// 受锁保护的设备和超级块
+struct FsInner<D: BlockDevice> {
+    device: D,
+    superblock: Superblock,
+    // 每个文件系统块对应的设备块数
+    sectors_per_block: u64,
+    // 下次分配数据块和inode时开始查找的位置
+    block_hint: u32,
+    inode_hint: u64,
 }
 
-impl MemoryBlockDevice {
-    pub fn new() -> Self {
-        // 初始化为1MB的内存块
-        let size = 1024 * 1024;
+impl<D: BlockDevice> SimpleFileSystem<D> {
+    pub fn new(device: D) -> Self {
         Self {
-            data: vec![0; size],
-            size: size as u64,
+            inner: Mutex::new(FsInner {
+                device,
+                superblock: Superblock::default(),
+                sectors_per_block: 1,
+                block_hint: 0,
+                inode_hint: 0,
+            }),
+            mounted: false,
         }
     }
-}
 
-// 简单文件系统实现
-pub struct SimpleFileSystem {
-    device: MemoryBlockDevice,
-    // 这里可以添加文件系统的其他字段，如inode表、目录结构等
-}
+    /// 挂载设备上已有的文件系统，设备为空时先格式化
+    pub fn init(&mut self) -> Result<(), FsError> {
+        {
+            let mut inner = self.inner.lock();
+            let device_block_size = inner.device.block_size();
+            if device_block_size == 0 || device_block_size > BLOCK_SIZE || !BLOCK_SIZE.is_multiple_of(device_block_size) {
+                return Err(FsError::InvalidArgument);
+            }
+            inner.sectors_per_block = (BLOCK_SIZE / device_block_size) as u64;
 
-impl SimpleFileSystem {
-    pub fn new(device: MemoryBlockDevice) -> Self {
-        Self {
-            device,
+            if !inner.mount()? {
+                inner.format()?;
+            }
+            inner.device.flush()?;
+        }
+        self.mounted = true;
+        Ok(())
+    }
+
+    fn with_inner<T>(&self, f: impl FnOnce(&mut FsInner<D>) -> Result<T, FsError>) -> Result<T, FsError> {
+        if !self.mounted {
+            return Err(FsError::NotMounted);
         }
+        f(&mut self.inner.lock())
     }
 
-    pub fn init(&mut self) {
-        // 初始化文件系统，如创建根目录、inode表等
-        // 这里只是一个占位实现
+    /// 与with_inner相同，但在操作完成后刷新设备
+    fn with_inner_sync<T>(&self, f: impl FnOnce(&mut FsInner<D>) -> Result<T, FsError>) -> Result<T, FsError> {
+        self.with_inner(|fs| {
+            let result = f(fs);
+            fs.device.flush()?;
+            result
+        })
     }
 }
 
-impl FileSystem for SimpleFileSystem {
-    fn read(&self, path: &str, offset: u64, length: u64) -> Result<Vec<u8>, &'static str> {
-        // 实现文件读取
-        Ok(Vec::new())
+impl<D: BlockDevice> FsInner<D> {
+    // ---- 设备访问 ----
+
+    fn block_count(&self) -> u32 {
+        (self.device.block_count() / self.sectors_per_block).min(u32::MAX as u64) as u32
+    }
+
+    fn read_block(&mut self, block: u32, buf: &mut [u8; BLOCK_SIZE]) -> Result<(), FsError> {
+        self.device.read_blocks(block as u64 * self.sectors_per_block, buf)
     }
 
-    fn write(&self, path: &str, offset: u64, data: &[u8]) -> Result<usize, &'static str> {
-        // 实现文件写入
-        Ok(data.len())
+    fn write_block(&mut self, block: u32, buf: &[u8; BLOCK_SIZE]) -> Result<(), FsError> {
+        self.device.write_blocks(block as u64 * self.sectors_per_block, buf)
+    }
+
+    // ---- 超级块 ----
+
+    fn mount(&mut self) -> Result<bool, FsError> {
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.read_block(0, &mut buf)?;
+        match Superblock::decode(&buf) {
+            Some(sb) if sb.total_blocks <= self.block_count() => {
+                self.superblock = sb;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn format(&mut self) -> Result<(), FsError> {
+        let sb = Superblock::for_device(self.block_count()).ok_or(FsError::NoSpace)?;
+        self.superblock = sb;
+
+        let zero = [0u8; BLOCK_SIZE];
+        for block in 0..sb.data_start {
+            self.write_block(block, &zero)?;
+        }
+        // 元数据区的块始终标记为已使用
+        for block in 0..sb.data_start {
+            self.set_block_used(block, true)?;
+        }
+        self.flush_superblock()?;
+
+        let root = self.alloc_inode(InodeKind::Directory)?;
+        debug_assert_eq!(root, ROOT_INODE);
+        self.add_entry(root, ".", root, true)?;
+        self.add_entry(root, "..", root, true)?;
+        let mut inode = self.read_inode(root)?;
+        inode.links = 2;
+        self.write_inode(root, &inode)
     }
 
-    fn create(&self, path: &str) -> Result<(), &'static str> {
-        // 实现文件创建
+    fn flush_superblock(&mut self) -> Result<(), FsError> {
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.superblock.encode(&mut buf);
+        self.write_block(0, &buf)
+    }
+
+    // ---- 块位图 ----
+
+    fn bitmap_location(&self, block: u32) -> (u32, usize, u8) {
+        let byte = block as usize / 8;
+        let bitmap_block = self.superblock.bitmap_start + (byte / BLOCK_SIZE) as u32;
+        (bitmap_block, byte % BLOCK_SIZE, 1 << (block % 8))
+    }
+
+    fn is_block_used(&mut self, block: u32) -> Result<bool, FsError> {
+        let (bitmap_block, offset, mask) = self.bitmap_location(block);
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.read_block(bitmap_block, &mut buf)?;
+        Ok(buf[offset] & mask != 0)
+    }
+
+    fn set_block_used(&mut self, block: u32, used: bool) -> Result<(), FsError> {
+        let (bitmap_block, offset, mask) = self.bitmap_location(block);
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.read_block(bitmap_block, &mut buf)?;
+        if used {
+            buf[offset] |= mask;
+        } else {
+            buf[offset] &= !mask;
+        }
+        self.write_block(bitmap_block, &buf)
+    }
+
+    /// 在[from, to)中查找空闲块，每个位图块只读一次，按64位字跳过已满的部分
+    fn find_free_block(&mut self, from: u32, to: u32) -> Result<Option<u32>, FsError> {
+        let bits_per_block = (BLOCK_SIZE * 8) as u32;
+        let mut buf = [0u8; BLOCK_SIZE];
+        let mut block = from;
+        while block < to {
+            let bitmap_index = block / bits_per_block;
+            self.read_block(self.superblock.bitmap_start + bitmap_index, &mut buf)?;
+            let end = (bitmap_index + 1).saturating_mul(bits_per_block).min(to);
+            while block < end {
+                let word_start = block & !63;
+                let offset = (word_start % bits_per_block) as usize / 8;
+                let word = u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap());
+                // 忽略字中block之前的位
+                let free = !word & (u64::MAX << (block - word_start));
+                if free != 0 {
+                    let found = word_start + free.trailing_zeros();
+                    return Ok((found < to).then_some(found));
+                }
+                block = word_start.saturating_add(64);
+            }
+        }
+        Ok(None)
+    }
+
+    fn alloc_block(&mut self) -> Result<u32, FsError> {
+        let sb = self.superblock;
+        // 从上次分配的位置向后找，找不到再从数据区开头找到该位置
+        let hint = self.block_hint.clamp(sb.data_start, sb.total_blocks);
+        let block = match self.find_free_block(hint, sb.total_blocks)? {
+            Some(block) => block,
+            None => self.find_free_block(sb.data_start, hint)?.ok_or(FsError::NoSpace)?,
+        };
+        self.block_hint = block + 1;
+
+        self.set_block_used(block, true)?;
+        self.write_block(block, &[0u8; BLOCK_SIZE])?;
+        self.superblock.free_blocks -= 1;
+        self.flush_superblock()?;
+        Ok(block)
+    }
+
+    fn free_block(&mut self, block: u32) -> Result<(), FsError> {
+        if block < self.superblock.data_start || !self.is_block_used(block)? {
+            return Ok(());
+        }
+        self.set_block_used(block, false)?;
+        self.superblock.free_blocks += 1;
+        self.flush_superblock()
+    }
+
+    // ---- inode表 ----
+
+    fn inode_location(&self, id: u64) -> Result<(u32, usize), FsError> {
+        if id >= self.superblock.inode_count as u64 {
+            return Err(FsError::InvalidArgument);
+        }
+        let block = self.superblock.inode_table_start + (id as usize / INODES_PER_BLOCK) as u32;
+        Ok((block, (id as usize % INODES_PER_BLOCK) * INODE_SIZE))
+    }
+
+    fn read_inode(&mut self, id: u64) -> Result<Inode, FsError> {
+        let (block, offset) = self.inode_location(id)?;
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.read_block(block, &mut buf)?;
+        Ok(Inode::decode(&buf[offset..offset + INODE_SIZE]))
+    }
+
+    fn write_inode(&mut self, id: u64, inode: &Inode) -> Result<(), FsError> {
+        let (block, offset) = self.inode_location(id)?;
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.read_block(block, &mut buf)?;
+        inode.encode(&mut buf[offset..offset + INODE_SIZE]);
+        self.write_block(block, &buf)?;
         Ok(())
     }
 
-    fn delete(&self, path: &str) -> Result<(), &'static str> {
-        // 实现文件删除
+    /// 在[from, to)中查找空闲inode，inode表的每个块只读一次
+    fn find_free_inode(&mut self, from: u64, to: u64) -> Result<Option<u64>, FsError> {
+        let mut buf = [0u8; BLOCK_SIZE];
+        let mut id = from;
+        while id < to {
+            let (block, _) = self.inode_location(id)?;
+            self.read_block(block, &mut buf)?;
+            let end = (id / INODES_PER_BLOCK as u64 + 1) * INODES_PER_BLOCK as u64;
+            while id < end.min(to) {
+                let offset = (id as usize % INODES_PER_BLOCK) * INODE_SIZE;
+                if Inode::decode(&buf[offset..offset + INODE_SIZE]).kind == InodeKind::Free {
+                    return Ok(Some(id));
+                }
+                id += 1;
+            }
+        }
+        Ok(None)
+    }
+
+    fn alloc_inode(&mut self, kind: InodeKind) -> Result<u64, FsError> {
+        let count = self.superblock.inode_count as u64;
+        let hint = self.inode_hint.min(count);
+        let id = match self.find_free_inode(hint, count)? {
+            Some(id) => id,
+            None => self.find_free_inode(0, hint)?.ok_or(FsError::NoSpace)?,
+        };
+        self.inode_hint = id + 1;
+
+        // 保留释放时递增的代数，旧句柄不会把新文件当成原来的文件
+        let generation = self.read_inode(id)?.generation;
+        self.write_inode(id, &Inode { generation, ..Inode::new(kind) })?;
+        self.superblock.free_inodes -= 1;
+        self.flush_superblock()?;
+        Ok(id)
+    }
+
+    fn free_inode(&mut self, id: u64) -> Result<(), FsError> {
+        let mut inode = self.read_inode(id)?;
+        self.free_data(&mut inode)?;
+        let generation = inode.generation.wrapping_add(1);
+        self.write_inode(id, &Inode { generation, ..Inode::empty() })?;
+        self.superblock.free_inodes += 1;
+        self.flush_superblock()
+    }
+
+    // ---- 文件数据 ----
+
+    /// 查找文件第index个数据块对应的设备块，空洞返回0
+    fn block_at(&mut self, inode: &Inode, index: usize) -> Result<u32, FsError> {
+        if index < DIRECT_BLOCKS {
+            return Ok(inode.direct[index]);
+        }
+        let index = index - DIRECT_BLOCKS;
+        if index >= POINTERS_PER_BLOCK || inode.indirect == 0 {
+            return Ok(0);
+        }
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.read_block(inode.indirect, &mut buf)?;
+        Ok(layout::read_u32(&buf, index * 4))
+    }
+
+    /// 与block_at相同，但会为空洞分配新块
+    fn block_at_or_alloc(&mut self, inode: &mut Inode, index: usize) -> Result<u32, FsError> {
+        if index < DIRECT_BLOCKS {
+            if inode.direct[index] == 0 {
+                inode.direct[index] = self.alloc_block()?;
+            }
+            return Ok(inode.direct[index]);
+        }
+
+        let index = index - DIRECT_BLOCKS;
+        if index >= POINTERS_PER_BLOCK {
+            return Err(FsError::FileTooLarge);
+        }
+        if inode.indirect == 0 {
+            inode.indirect = self.alloc_block()?;
+        }
+
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.read_block(inode.indirect, &mut buf)?;
+        let mut block = layout::read_u32(&buf, index * 4);
+        if block == 0 {
+            block = self.alloc_block()?;
+            layout::write_u32(&mut buf, index * 4, block);
+            self.write_block(inode.indirect, &buf)?;
+        }
+        Ok(block)
+    }
+
+    fn read_data(&mut self, inode: &Inode, offset: u64, length: u64) -> Result<Vec<u8>, FsError> {
+        if offset >= inode.size {
+            return Ok(Vec::new());
+        }
+        let end = inode.size.min(offset.saturating_add(length));
+        let mut data = Vec::with_capacity((end - offset) as usize);
+        let mut buf = [0u8; BLOCK_SIZE];
+        let mut pos = offset;
+
+        while pos < end {
+            let index = (pos / BLOCK_SIZE as u64) as usize;
+            let start = (pos % BLOCK_SIZE as u64) as usize;
+            let len = (BLOCK_SIZE - start).min((end - pos) as usize);
+
+            match self.block_at(inode, index)? {
+                0 => data.resize(data.len() + len, 0),
+                block => {
+                    self.read_block(block, &mut buf)?;
+                    data.extend_from_slice(&buf[start..start + len]);
+                }
+            }
+            pos += len as u64;
+        }
+        Ok(data)
+    }
+
+    fn write_data(&mut self, id: u64, inode: &mut Inode, offset: u64, data: &[u8]) -> Result<usize, FsError> {
+        // 偏移可以被seek到任意位置，相加时要防止溢出
+        offset
+            .checked_add(data.len() as u64)
+            .filter(|&end| end <= MAX_FILE_SIZE)
+            .ok_or(FsError::FileTooLarge)?;
+
+        let mut written = 0;
+        let mut result = Ok(());
+
+        while written < data.len() {
+            match self.write_chunk(inode, offset + written as u64, &data[written..]) {
+                Ok(len) => written += len,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        // 即使中途空间不足，也要保存已写入部分和新分配的块
+        inode.size = inode.size.max(offset + written as u64);
+        self.write_inode(id, inode)?;
+        result.map(|_| written)
+    }
+
+    /// 在pos处写入不超过一个块的数据，返回写入的字节数
+    fn write_chunk(&mut self, inode: &mut Inode, pos: u64, data: &[u8]) -> Result<usize, FsError> {
+        let index = (pos / BLOCK_SIZE as u64) as usize;
+        let start = (pos % BLOCK_SIZE as u64) as usize;
+        let len = (BLOCK_SIZE - start).min(data.len());
+
+        let block = self.block_at_or_alloc(inode, index)?;
+        let mut buf = [0u8; BLOCK_SIZE];
+        self.read_block(block, &mut buf)?;
+        buf[start..start + len].copy_from_slice(&data[..len]);
+        self.write_block(block, &buf)?;
+        Ok(len)
+    }
+
+    /// 统计inode占用的块数（包括间接块本身）
+    fn allocated_blocks(&mut self, inode: &Inode) -> Result<u64, FsError> {
+        let mut count = inode.direct.iter().filter(|&&block| block != 0).count() as u64;
+        if inode.indirect != 0 {
+            let mut buf = [0u8; BLOCK_SIZE];
+            self.read_block(inode.indirect, &mut buf)?;
+            count += 1 + (0..POINTERS_PER_BLOCK)
+                .filter(|&i| layout::read_u32(&buf, i * 4) != 0)
+                .count() as u64;
+        }
+        Ok(count)
+    }
+
+    /// 释放文件第keep个数据块及之后的所有块
+    fn free_blocks_from(&mut self, inode: &mut Inode, keep: usize) -> Result<(), FsError> {
+        for i in keep.min(DIRECT_BLOCKS)..DIRECT_BLOCKS {
+            if inode.direct[i] != 0 {
+                self.free_block(inode.direct[i])?;
+                inode.direct[i] = 0;
+            }
+        }
+        if inode.indirect != 0 {
+            let first = keep.saturating_sub(DIRECT_BLOCKS);
+            let mut buf = [0u8; BLOCK_SIZE];
+            self.read_block(inode.indirect, &mut buf)?;
+            for i in first..POINTERS_PER_BLOCK {
+                let block = layout::read_u32(&buf, i * 4);
+                if block != 0 {
+                    self.free_block(block)?;
+                    layout::write_u32(&mut buf, i * 4, 0);
+                }
+            }
+            if first == 0 {
+                self.free_block(inode.indirect)?;
+                inode.indirect = 0;
+            } else {
+                self.write_block(inode.indirect, &buf)?;
+            }
+        }
         Ok(())
     }
 
-    fn copy_item(&self, src: &str, dst: &str, recursive: bool) -> Result<(), &'static str> {
-        // 实现文件复制
+    fn free_data(&mut self, inode: &mut Inode) -> Result<(), FsError> {
+        self.free_blocks_from(inode, 0)?;
+        inode.size = 0;
         Ok(())
     }
 
-    fn list_directory(&self, inode_id: u64) -> Result<Vec<(String, u64, bool, u64)>, &'static str> {
-        // 实现目录列出
-        // 这里返回一个示例目录项
-        Ok(vec![
-            (".".to_string(), 0, true, 0),
-            ("..".to_string(), 1, true, 0),
-            ("example.txt".to_string(), 2, false, 1024),
-        ])
+    /// 把文件截断或扩展到size字节，扩展部分读出为0
+    fn truncate(&mut self, id: u64, size: u64) -> Result<(), FsError> {
+        let mut inode = self.file_inode(id)?;
+        if size > MAX_FILE_SIZE {
+            return Err(FsError::FileTooLarge);
+        }
+
+        if size < inode.size {
+            let keep = size.div_ceil(BLOCK_SIZE as u64) as usize;
+            self.free_blocks_from(&mut inode, keep)?;
+
+            // 清零最后一个块中截断点之后的内容，保证以后扩展时读出为0
+            let tail = (size % BLOCK_SIZE as u64) as usize;
+            if tail != 0 {
+                let block = self.block_at(&inode, keep - 1)?;
+                if block != 0 {
+                    let mut buf = [0u8; BLOCK_SIZE];
+                    self.read_block(block, &mut buf)?;
+                    buf[tail..].fill(0);
+                    self.write_block(block, &buf)?;
+                }
+            }
+        }
+
+        inode.size = size;
+        self.write_inode(id, &inode)
     }
 
-    fn create_directory(&self, path: &str, parent_inode_id: u64) -> Result<u64, &'static str> {
-        // 实现目录创建
-        // 这里返回一个示例inode_id
-        Ok(100)
+    // ---- 目录 ----
+
+    fn read_dir(&mut self, dir_id: u64) -> Result<Vec<(u64, DirEntry)>, FsError> {
+        let inode = self.read_inode(dir_id)?;
+        if !inode.is_dir() {
+            return Err(FsError::NotADirectory);
+        }
+        let data = self.read_data(&inode, 0, inode.size)?;
+        Ok(data
+            .chunks_exact(DIR_ENTRY_SIZE)
+            .enumerate()
+            .map(|(slot, raw)| ((slot * DIR_ENTRY_SIZE) as u64, DirEntry::decode(raw)))
+            .filter(|(_, entry)| entry.is_used())
+            .collect())
     }
 
-    fn delete_item(&self, path: &str, recursive: bool) -> Result<(), &'static str> {
-        // 实现文件/目录删除
+    fn lookup(&mut self, dir_id: u64, name: &str) -> Result<Option<u64>, FsError> {
+        Ok(self
+            .read_dir(dir_id)?
+            .into_iter()
+            .find(|(_, entry)| entry.name() == name)
+            .map(|(_, entry)| entry.inode as u64))
+    }
+
+    fn add_entry(&mut self, dir_id: u64, name: &str, child: u64, is_dir: bool) -> Result<(), FsError> {
+        let mut inode = self.read_inode(dir_id)?;
+        if !inode.is_dir() {
+            return Err(FsError::NotADirectory);
+        }
+
+        // 优先复用已删除目录项留下的空槽
+        let data = self.read_data(&inode, 0, inode.size)?;
+        let offset = data
+            .chunks_exact(DIR_ENTRY_SIZE)
+            .position(|raw| !DirEntry::decode(raw).is_used())
+            .map(|slot| (slot * DIR_ENTRY_SIZE) as u64)
+            .unwrap_or(inode.size);
+
+        let mut raw = [0u8; DIR_ENTRY_SIZE];
+        DirEntry::new(name, child, is_dir).encode(&mut raw);
+        self.write_data(dir_id, &mut inode, offset, &raw)?;
         Ok(())
     }
 
-    fn move_item(&self, src: &str, dst: &str) -> Result<(), &'static str> {
-        // 实现文件移动
+    fn remove_entry(&mut self, dir_id: u64, name: &str) -> Result<(), FsError> {
+        let offset = self
+            .read_dir(dir_id)?
+            .into_iter()
+            .find(|(_, entry)| entry.name() == name)
+            .map(|(offset, _)| offset)
+            .ok_or(FsError::NotFound)?;
+        let mut inode = self.read_inode(dir_id)?;
+        self.write_data(dir_id, &mut inode, offset, &[0u8; DIR_ENTRY_SIZE])?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// 读取普通文件的inode，已释放或为目录时报错
+    fn file_inode(&mut self, id: u64) -> Result<Inode, FsError> {
+        let inode = self.read_inode(id)?;
+        match inode.kind {
+            InodeKind::Free => Err(FsError::StaleHandle),
+            InodeKind::Directory => Err(FsError::IsADirectory),
+            InodeKind::File => Ok(inode),
+        }
+    }
+
+    fn metadata(&mut self, id: u64) -> Result<Metadata, FsError> {
+        let inode = self.read_inode(id)?;
+        if inode.kind == InodeKind::Free {
+            return Err(FsError::StaleHandle);
+        }
+        Ok(Metadata {
+            inode_id: id,
+            is_dir: inode.is_dir(),
+            size: inode.size,
+            blocks: self.allocated_blocks(&inode)?,
+            generation: inode.generation,
+        })
+    }
+
+    fn is_empty_dir(&mut self, dir_id: u64) -> Result<bool, FsError> {
+        Ok(self
+            .read_dir(dir_id)?
+            .iter()
+            .all(|(_, entry)| entry.name() == "." || entry.name() == ".."))
+    }
+
+    /// 检查ancestor是否为dir_id本身或其祖先目录
+    fn is_ancestor(&mut self, ancestor: u64, mut dir_id: u64) -> Result<bool, FsError> {
+        loop {
+            if dir_id == ancestor {
+                return Ok(true);
+            }
+            if dir_id == ROOT_INODE {
+                return Ok(false);
+            }
+            dir_id = self.lookup(dir_id, "..")?.ok_or(FsError::Corrupted)?;
+        }
+    }
+
+    // ---- 路径解析 ----
+
+    /// 从start开始解析路径，以'/'开头的路径从根目录开始
+    fn resolve_from(&mut self, start: u64, path: &str) -> Result<u64, FsError> {
+        let mut current = if path.starts_with('/') { ROOT_INODE } else { start };
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            current = self.lookup(current, component)?.ok_or(FsError::NotFound)?;
+        }
+        Ok(current)
+    }
+
+    fn resolve(&mut self, path: &str) -> Result<u64, FsError> {
+        self.resolve_from(ROOT_INODE, path)
+    }
+
+    /// 解析路径的父目录，返回父目录inode和最后一级名称
+    fn resolve_parent_from<'p>(&mut self, start: u64, path: &'p str) -> Result<(u64, &'p str), FsError> {
+        let trimmed = path.trim_end_matches('/');
+        let (parent_path, name) = match trimmed.rfind('/') {
+            Some(pos) => (&trimmed[..pos + 1], &trimmed[pos + 1..]),
+            None => ("", trimmed),
+        };
+
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(FsError::InvalidArgument);
+        }
+        if name.len() > MAX_NAME_LEN {
+            return Err(FsError::NameTooLong);
+        }
+
+        let parent = self.resolve_from(start, parent_path)?;
+        if !self.read_inode(parent)?.is_dir() {
+            return Err(FsError::NotADirectory);
+        }
+        Ok((parent, name))
+    }
+
+    fn resolve_parent<'p>(&mut self, path: &'p str) -> Result<(u64, &'p str), FsError> {
+        self.resolve_parent_from(ROOT_INODE, path)
+    }
+
+    /// 确定mv/cp的目标位置：目标为已有目录时放入该目录，否则按新路径创建
+    fn resolve_target<'p>(&mut self, dst: &'p str, src_name: &'p str) -> Result<(u64, &'p str), FsError> {
+        match self.resolve(dst) {
+            Ok(id) if self.read_inode(id)?.is_dir() => {
+                if self.lookup(id, src_name)?.is_some() {
+                    return Err(FsError::AlreadyExists);
+                }
+                Ok((id, src_name))
+            }
+            Ok(_) => Err(FsError::AlreadyExists),
+            Err(_) => {
+                let (parent, name) = self.resolve_parent(dst)?;
+                if self.lookup(parent, name)?.is_some() {
+                    return Err(FsError::AlreadyExists);
+                }
+                Ok((parent, name))
+            }
+        }
+    }
+
+    // ---- 高层操作 ----
+
+    fn make_node(&mut self, parent: u64, name: &str, kind: InodeKind) -> Result<u64, FsError> {
+        if self.lookup(parent, name)?.is_some() {
+            return Err(FsError::AlreadyExists);
+        }
+
+        let id = self.alloc_inode(kind)?;
+        let is_dir = kind == InodeKind::Directory;
+        let result = (|| {
+            if is_dir {
+                self.add_entry(id, ".", id, true)?;
+                self.add_entry(id, "..", parent, true)?;
+                let mut inode = self.read_inode(id)?;
+                inode.links = 2;
+                self.write_inode(id, &inode)?;
+            }
+            self.add_entry(parent, name, id, is_dir)
+        })();
+
+        if let Err(e) = result {
+            self.free_inode(id)?;
+            return Err(e);
+        }
+        if is_dir {
+            self.adjust_links(parent, 1)?;
+        }
+        Ok(id)
+    }
+
+    fn adjust_links(&mut self, id: u64, delta: i32) -> Result<(), FsError> {
+        let mut inode = self.read_inode(id)?;
+        inode.links = (inode.links as i32 + delta).max(0) as u16;
+        self.write_inode(id, &inode)
+    }
+
+    fn remove_tree(&mut self, id: u64) -> Result<(), FsError> {
+        if self.read_inode(id)?.is_dir() {
+            for (_, entry) in self.read_dir(id)? {
+                if entry.name() != "." && entry.name() != ".." {
+                    self.remove_tree(entry.inode as u64)?;
+                }
+            }
+        }
+        self.free_inode(id)
+    }
+
+    fn copy_tree(&mut self, src: u64, parent: u64, name: &str) -> Result<(), FsError> {
+        let inode = self.read_inode(src)?;
+        if inode.is_dir() {
+            let new_dir = self.make_node(parent, name, InodeKind::Directory)?;
+            for (_, entry) in self.read_dir(src)? {
+                if entry.name() != "." && entry.name() != ".." {
+                    self.copy_tree(entry.inode as u64, new_dir, entry.name())?;
+                }
+            }
+        } else {
+            let new_file = self.make_node(parent, name, InodeKind::File)?;
+            let data = self.read_data(&inode, 0, inode.size)?;
+            let mut new_inode = self.read_inode(new_file)?;
+            self.write_data(new_file, &mut new_inode, 0, &data)?;
+        }
+        Ok(())
+    }
+
+    fn delete_item(&mut self, path: &str, recursive: bool) -> Result<(), FsError> {
+        let (parent, name) = self.resolve_parent(path)?;
+        let id = self.lookup(parent, name)?.ok_or(FsError::NotFound)?;
+        let is_dir = self.read_inode(id)?.is_dir();
+
+        if is_dir && !recursive && !self.is_empty_dir(id)? {
+            return Err(FsError::DirectoryNotEmpty);
+        }
+
+        self.remove_entry(parent, name)?;
+        self.remove_tree(id)?;
+        if is_dir {
+            self.adjust_links(parent, -1)?;
+        }
+        Ok(())
+    }
+}
+
+impl<D: BlockDevice> FileSystem for SimpleFileSystem<D> {
+    fn read(&self, path: &str, offset: u64, length: u64) -> Result<Vec<u8>, FsError> {
+        self.with_inner(|fs| {
+            let id = fs.resolve(path)?;
+            let inode = fs.file_inode(id)?;
+            fs.read_data(&inode, offset, length)
+        })
+    }
+
+    fn write(&self, path: &str, offset: u64, data: &[u8]) -> Result<usize, FsError> {
+        self.with_inner_sync(|fs| {
+            let id = fs.resolve(path)?;
+            let mut inode = fs.file_inode(id)?;
+            fs.write_data(id, &mut inode, offset, data)
+        })
+    }
+
+    fn create(&self, path: &str) -> Result<(), FsError> {
+        self.with_inner_sync(|fs| {
+            let (parent, name) = fs.resolve_parent(path)?;
+            fs.make_node(parent, name, InodeKind::File).map(|_| ())
+        })
+    }
+
+    fn copy_item(&self, src: &str, dst: &str, recursive: bool) -> Result<(), FsError> {
+        self.with_inner_sync(|fs| {
+            let (_, src_name) = fs.resolve_parent(src)?;
+            let src_id = fs.resolve(src)?;
+            if fs.read_inode(src_id)?.is_dir() && !recursive {
+                return Err(FsError::IsADirectory);
+            }
+
+            let (parent, name) = fs.resolve_target(dst, src_name)?;
+            if fs.is_ancestor(src_id, parent)? {
+                return Err(FsError::InvalidArgument);
+            }
+            fs.copy_tree(src_id, parent, name)
+        })
+    }
+
+    fn list_directory(&self, inode_id: u64) -> Result<Vec<(String, u64, bool, u64)>, FsError> {
+        self.with_inner(|fs| {
+            let mut entries = Vec::new();
+            for (_, entry) in fs.read_dir(inode_id)? {
+                let size = fs.read_inode(entry.inode as u64)?.size;
+                entries.push((entry.name().to_string(), entry.inode as u64, entry.is_dir, size));
+            }
+            Ok(entries)
+        })
+    }
+
+    fn create_directory(&self, path: &str, parent_inode_id: u64) -> Result<u64, FsError> {
+        self.with_inner_sync(|fs| {
+            let (parent, name) = fs.resolve_parent_from(parent_inode_id, path)?;
+            fs.make_node(parent, name, InodeKind::Directory)
+        })
+    }
+
+    fn delete_item(&self, path: &str, recursive: bool) -> Result<(), FsError> {
+        self.with_inner_sync(|fs| fs.delete_item(path, recursive))
+    }
+
+    fn move_item(&self, src: &str, dst: &str) -> Result<(), FsError> {
+        self.with_inner_sync(|fs| {
+            let (src_parent, src_name) = fs.resolve_parent(src)?;
+            let id = fs.lookup(src_parent, src_name)?.ok_or(FsError::NotFound)?;
+            let is_dir = fs.read_inode(id)?.is_dir();
+
+            let (parent, name) = fs.resolve_target(dst, src_name)?;
+            if is_dir && fs.is_ancestor(id, parent)? {
+                return Err(FsError::InvalidArgument);
+            }
+
+            fs.add_entry(parent, name, id, is_dir)?;
+            fs.remove_entry(src_parent, src_name)?;
+
+            if is_dir && parent != src_parent {
+                fs.remove_entry(id, "..")?;
+                fs.add_entry(id, "..", parent, true)?;
+                fs.adjust_links(src_parent, -1)?;
+                fs.adjust_links(parent, 1)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn metadata(&self, path: &str) -> Result<Metadata, FsError> {
+        self.with_inner(|fs| {
+            let id = fs.resolve(path)?;
+            fs.metadata(id)
+        })
+    }
+
+    fn read_at(&self, inode_id: u64, offset: u64, length: u64) -> Result<Vec<u8>, FsError> {
+        self.with_inner(|fs| {
+            let inode = fs.file_inode(inode_id)?;
+            fs.read_data(&inode, offset, length)
+        })
+    }
+
+    fn write_at(&self, inode_id: u64, offset: u64, data: &[u8]) -> Result<usize, FsError> {
+        self.with_inner_sync(|fs| {
+            let mut inode = fs.file_inode(inode_id)?;
+            fs.write_data(inode_id, &mut inode, offset, data)
+        })
+    }
+
+    fn truncate(&self, inode_id: u64, size: u64) -> Result<(), FsError> {
+        self.with_inner_sync(|fs| fs.truncate(inode_id, size))
+    }
+
+    fn stat(&self, inode_id: u64) -> Result<Metadata, FsError> {
+        self.with_inner(|fs| fs.metadata(inode_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    // 在内存设备上格式化一个新的文件系统
+    fn new_fs() -> SimpleFileSystem {
+        let mut fs = SimpleFileSystem::new(MemoryBlockDevice::with_size(256 * 1024));
+        fs.init().unwrap();
+        fs
+    }
+
+    #[test_case]
+    fn create_write_read() {
+        let fs = new_fs();
+        fs.create("/a").unwrap();
+        assert_eq!(fs.create("/a"), Err(FsError::AlreadyExists));
+
+        assert_eq!(fs.write("/a", 0, b"hello world"), Ok(11));
+        assert_eq!(fs.read("/a", 0, 64).unwrap(), b"hello world");
+        assert_eq!(fs.read("/a", 6, 3).unwrap(), b"wor");
+        assert_eq!(fs.metadata("/a").unwrap().size, 11);
+    }
+
+    #[test_case]
+    fn write_past_the_end_reads_back_zeros() {
+        let fs = new_fs();
+        fs.create("/a").unwrap();
+        fs.write("/a", BLOCK_SIZE as u64 + 2, b"x").unwrap();
+
+        let data = fs.read("/a", 0, MAX_FILE_SIZE).unwrap();
+        assert_eq!(data.len(), BLOCK_SIZE + 3);
+        assert!(data[..BLOCK_SIZE + 2].iter().all(|&b| b == 0));
+        assert_eq!(data[BLOCK_SIZE + 2], b'x');
+    }
+
+    #[test_case]
+    fn indirect_blocks() {
+        let fs = new_fs();
+        fs.create("/big").unwrap();
+        let data: Vec<u8> = (0..(DIRECT_BLOCKS + 3) * BLOCK_SIZE).map(|i| (i % 251) as u8).collect();
+
+        assert_eq!(fs.write("/big", 0, &data), Ok(data.len()));
+        assert_eq!(fs.read("/big", 0, MAX_FILE_SIZE).unwrap(), data);
+        // 数据块加上一个间接块
+        assert_eq!(fs.metadata("/big").unwrap().blocks, (DIRECT_BLOCKS + 3 + 1) as u64);
+        assert_eq!(fs.write("/big", MAX_FILE_SIZE, b"x"), Err(FsError::FileTooLarge));
+    }
+
+    #[test_case]
+    fn truncate_frees_blocks_and_zeroes_the_tail() {
+        let fs = new_fs();
+        fs.create("/a").unwrap();
+        fs.write("/a", 0, &vec![0xAA; (DIRECT_BLOCKS + 2) * BLOCK_SIZE]).unwrap();
+        let id = fs.metadata("/a").unwrap().inode_id;
+
+        fs.truncate(id, 10).unwrap();
+        let meta = fs.metadata("/a").unwrap();
+        assert_eq!(meta.size, 10);
+        assert_eq!(meta.blocks, 1);
+
+        // 重新扩展后截断点之后读出为0
+        fs.truncate(id, 20).unwrap();
+        let data = fs.read("/a", 0, 64).unwrap();
+        assert_eq!(data[..10], [0xAA; 10]);
+        assert_eq!(data[10..], [0; 10]);
+    }
+
+    #[test_case]
+    fn move_into_own_descendant_is_rejected() {
+        let fs = new_fs();
+        fs.create_directory("/a", ROOT_INODE).unwrap();
+        fs.create_directory("/a/b", ROOT_INODE).unwrap();
+
+        assert_eq!(fs.move_item("/a", "/a/b"), Err(FsError::InvalidArgument));
+        assert_eq!(fs.copy_item("/a", "/a/b", true), Err(FsError::InvalidArgument));
+
+        fs.create_directory("/c", ROOT_INODE).unwrap();
+        fs.move_item("/a", "/c").unwrap();
+        assert!(fs.metadata("/c/a/b").unwrap().is_dir);
+        assert_eq!(fs.metadata("/a").map(|_| ()), Err(FsError::NotFound));
+    }
+}
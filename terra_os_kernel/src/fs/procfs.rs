@@ -0,0 +1,186 @@
+// 进程信息文件系统
+// 挂载在/proc，文件内容在读取时根据内核状态动态生成，只读
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::new_fs::{FileSystem, FsError, Metadata};
+use crate::allocator::LinkedListAllocator;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ProcFile {
+    MemInfo,
+    Mounts,
+    Version,
+}
+
+// (名称, inode编号, 文件)，根目录的inode编号为0
+const FILES: [(&str, u64, ProcFile); 3] = [
+    ("meminfo", 1, ProcFile::MemInfo),
+    ("mounts", 2, ProcFile::Mounts),
+    ("version", 3, ProcFile::Version),
+];
+
+pub struct ProcFs {
+    allocator: &'static LinkedListAllocator,
+}
+
+impl ProcFs {
+    pub const fn new(allocator: &'static LinkedListAllocator) -> Self {
+        ProcFs { allocator }
+    }
+
+    fn find(path: &str) -> Result<Option<(u64, ProcFile)>, FsError> {
+        let name = path.trim_matches('/');
+        if name.is_empty() {
+            return Ok(None);
+        }
+        FILES
+            .iter()
+            .find(|(file_name, _, _)| *file_name == name)
+            .map(|&(_, ino, file)| Some((ino, file)))
+            .ok_or(FsError::NotFound)
+    }
+
+    fn file_by_inode(inode_id: u64) -> Result<ProcFile, FsError> {
+        if inode_id == 0 {
+            return Err(FsError::IsADirectory);
+        }
+        FILES
+            .iter()
+            .find(|(_, ino, _)| *ino == inode_id)
+            .map(|&(_, _, file)| file)
+            .ok_or(FsError::StaleHandle)
+    }
+
+    fn read_file(&self, file: ProcFile, offset: u64, length: u64) -> Vec<u8> {
+        let content = self.generate(file);
+        let bytes = content.as_bytes();
+        let start = (offset as usize).min(bytes.len());
+        let end = start.saturating_add(length as usize).min(bytes.len());
+        bytes[start..end].to_vec()
+    }
+
+    fn generate(&self, file: ProcFile) -> String {
+        match file {
+            ProcFile::MemInfo => {
+                let mut content = String::new();
+                if let Some(frames) = crate::memory::frame_stats() {
+                    content.push_str(&format!(
+                        "MemTotal:  {} kB\nMemFree:   {} kB\n",
+                        frames.total_frames * 4,
+                        frames.free_frames * 4,
+                    ));
+                }
+                let stats = self.allocator.get_memory_stats();
+                content.push_str(&format!(
+                    "HeapTotal: {} B\nHeapLimit: {} B\nHeapGrows: {}\nHeapUsed:  {} B\nHeapFree:  {} B\nHeapPeak:  {} B\nAllocs:    {}\nFrees:     {}\n",
+                    stats.total_heap_size,
+                    stats.heap_limit,
+                    stats.growth_count,
+                    stats.current_allocated,
+                    stats.free_memory,
+                    stats.max_allocated,
+                    stats.allocation_count,
+                    stats.deallocation_count,
+                ));
+                content
+            }
+            ProcFile::Mounts => {
+                let mut content = String::new();
+                if let Ok(vfs) = super::vfs() {
+                    for (path, fs_type) in vfs.mounts() {
+                        content.push_str(&format!("{} {}\n", fs_type, path));
+                    }
+                }
+                content
+            }
+            ProcFile::Version => "TerraOS 0.1.0 (Rust Kernel, x86_64)\n".to_string(),
+        }
+    }
+}
+
+impl FileSystem for ProcFs {
+    fn read(&self, path: &str, offset: u64, length: u64) -> Result<Vec<u8>, FsError> {
+        let (_, file) = Self::find(path)?.ok_or(FsError::IsADirectory)?;
+        Ok(self.read_file(file, offset, length))
+    }
+
+    fn write(&self, _path: &str, _offset: u64, _data: &[u8]) -> Result<usize, FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn create(&self, _path: &str) -> Result<(), FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn copy_item(&self, _src: &str, _dst: &str, _recursive: bool) -> Result<(), FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn list_directory(&self, inode_id: u64) -> Result<Vec<(String, u64, bool, u64)>, FsError> {
+        if inode_id != 0 {
+            return Err(FsError::NotADirectory);
+        }
+        let mut entries = vec![(".".to_string(), 0, true, 0), ("..".to_string(), 0, true, 0)];
+        for (name, ino, file) in FILES.iter() {
+            entries.push((name.to_string(), *ino, false, self.generate(*file).len() as u64));
+        }
+        Ok(entries)
+    }
+
+    fn create_directory(&self, _path: &str, _parent_inode_id: u64) -> Result<u64, FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn delete_item(&self, _path: &str, _recursive: bool) -> Result<(), FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn move_item(&self, _src: &str, _dst: &str) -> Result<(), FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn metadata(&self, path: &str) -> Result<Metadata, FsError> {
+        let (inode_id, is_dir, size) = match Self::find(path)? {
+            Some((ino, file)) => (ino, false, self.generate(file).len() as u64),
+            None => (0, true, 0),
+        };
+        Ok(Metadata {
+            inode_id,
+            is_dir,
+            size,
+            blocks: 0,
+            generation: 0,
+        })
+    }
+
+    fn read_at(&self, inode_id: u64, offset: u64, length: u64) -> Result<Vec<u8>, FsError> {
+        Ok(self.read_file(Self::file_by_inode(inode_id)?, offset, length))
+    }
+
+    fn write_at(&self, _inode_id: u64, _offset: u64, _data: &[u8]) -> Result<usize, FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn truncate(&self, _inode_id: u64, _size: u64) -> Result<(), FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn stat(&self, inode_id: u64) -> Result<Metadata, FsError> {
+        let (is_dir, size) = if inode_id == 0 {
+            (true, 0)
+        } else {
+            (false, self.generate(Self::file_by_inode(inode_id)?).len() as u64)
+        };
+        Ok(Metadata {
+            inode_id,
+            is_dir,
+            size,
+            blocks: 0,
+            generation: 0,
+        })
+    }
+}
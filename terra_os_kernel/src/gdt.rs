@@ -0,0 +1,83 @@
+// 全局描述符表(GDT)和任务状态段(TSS)
+// 内核自己的GDT，包含内核代码/数据段、用户代码/数据段和TSS
+// TSS的中断栈表(IST)为双重错误和NMI提供独立的栈，即使内核栈溢出也能正常处理
+
+use lazy_static::lazy_static;
+use x86_64::structures::gdt::{Descriptor, GlobalDescriptorTable, SegmentSelector};
+use x86_64::structures::tss::TaskStateSegment;
+use x86_64::VirtAddr;
+
+/// 双重错误使用的IST下标
+pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+/// NMI使用的IST下标
+pub const NMI_IST_INDEX: u16 = 1;
+
+// 每个IST栈的大小
+const IST_STACK_SIZE: usize = 4096 * 5;
+
+// IST栈本身，放在静态内存中，不依赖堆和页表映射
+static mut DOUBLE_FAULT_STACK: [u8; IST_STACK_SIZE] = [0; IST_STACK_SIZE];
+static mut NMI_STACK: [u8; IST_STACK_SIZE] = [0; IST_STACK_SIZE];
+
+lazy_static! {
+    static ref TSS: TaskStateSegment = {
+        let mut tss = TaskStateSegment::new();
+        // 栈向下增长，所以填入栈的末尾地址
+        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = {
+            let stack_start = VirtAddr::from_ptr(core::ptr::addr_of!(DOUBLE_FAULT_STACK));
+            stack_start + IST_STACK_SIZE
+        };
+        tss.interrupt_stack_table[NMI_IST_INDEX as usize] = {
+            let stack_start = VirtAddr::from_ptr(core::ptr::addr_of!(NMI_STACK));
+            stack_start + IST_STACK_SIZE
+        };
+        tss
+    };
+}
+
+/// GDT中各个段的选择子
+pub struct Selectors {
+    pub kernel_code: SegmentSelector,
+    pub kernel_data: SegmentSelector,
+    // 预留给用户态：进入ring 3时要用这两个选择子，目前还没有用户态
+    #[allow(dead_code)]
+    pub user_code: SegmentSelector,
+    #[allow(dead_code)]
+    pub user_data: SegmentSelector,
+    pub tss: SegmentSelector,
+}
+
+lazy_static! {
+    static ref GDT: (GlobalDescriptorTable, Selectors) = {
+        let mut gdt = GlobalDescriptorTable::new();
+        let kernel_code = gdt.add_entry(Descriptor::kernel_code_segment());
+        let kernel_data = gdt.add_entry(Descriptor::kernel_data_segment());
+        // 用户段的顺序(先数据后代码)满足syscall/sysret的要求
+        let user_data = gdt.add_entry(Descriptor::user_data_segment());
+        let user_code = gdt.add_entry(Descriptor::user_code_segment());
+        let tss = gdt.add_entry(Descriptor::tss_segment(&TSS));
+        (gdt, Selectors { kernel_code, kernel_data, user_code, user_data, tss })
+    };
+}
+
+/// 加载GDT，重新设置段寄存器并加载TSS，必须在init_idt之前调用
+pub fn init() {
+    use x86_64::instructions::segmentation::{Segment, CS, DS, ES, SS};
+    use x86_64::instructions::tables::load_tss;
+
+    GDT.0.load();
+    unsafe {
+        CS::set_reg(GDT.1.kernel_code);
+        DS::set_reg(GDT.1.kernel_data);
+        ES::set_reg(GDT.1.kernel_data);
+        SS::set_reg(GDT.1.kernel_data);
+        load_tss(GDT.1.tss);
+    }
+}
+
+/// 获取GDT中的段选择子
+// 预留给用户态和syscall的初始化，目前还没有调用者
+#[allow(dead_code)]
+pub fn selectors() -> &'static Selectors {
+    &GDT.1
+}
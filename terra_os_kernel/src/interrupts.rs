@@ -0,0 +1,122 @@
+// 中断处理
+// 建立中断描述符表(IDT)，为CPU异常注册处理函数，避免异常直接导致三重错误重启
+
+use lazy_static::lazy_static;
+use pic8259::ChainedPics;
+use spin::Mutex;
+use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
+
+use crate::{fault_println, gdt};
+
+/// 主PIC的中断向量起始编号，0-31留给CPU异常
+pub const PIC_1_OFFSET: u8 = 32;
+/// 从PIC的中断向量起始编号
+pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
+
+/// 级联的两片8259 PIC
+pub static PICS: Mutex<ChainedPics> = Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
+
+/// 硬件中断在IDT中的向量号
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum InterruptIndex {
+    Timer = PIC_1_OFFSET,
+    Keyboard,
+}
+
+impl InterruptIndex {
+    fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    fn as_usize(self) -> usize {
+        usize::from(self.as_u8())
+    }
+}
+
+lazy_static! {
+    static ref IDT: InterruptDescriptorTable = {
+        let mut idt = InterruptDescriptorTable::new();
+        idt.divide_error.set_handler_fn(divide_error_handler);
+        idt.breakpoint.set_handler_fn(breakpoint_handler);
+        idt.invalid_opcode.set_handler_fn(invalid_opcode_handler);
+        idt.general_protection_fault.set_handler_fn(general_protection_fault_handler);
+        idt.page_fault.set_handler_fn(page_fault_handler);
+        // 双重错误和NMI切换到TSS中的独立栈，内核栈溢出时也能处理
+        unsafe {
+            idt.double_fault
+                .set_handler_fn(double_fault_handler)
+                .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
+            idt.non_maskable_interrupt
+                .set_handler_fn(nmi_handler)
+                .set_stack_index(gdt::NMI_IST_INDEX);
+        }
+        idt[InterruptIndex::Timer.as_usize()].set_handler_fn(timer_interrupt_handler);
+        idt[InterruptIndex::Keyboard.as_usize()].set_handler_fn(keyboard_interrupt_handler);
+        idt
+    };
+}
+
+/// 加载IDT，应在启动时尽早调用
+pub fn init_idt() {
+    IDT.load();
+}
+
+/// 重新映射并初始化PIC，之后开中断即可接收硬件中断
+pub fn init_pics() {
+    unsafe { PICS.lock().initialize() };
+}
+
+// 断点异常可以恢复，打印后返回继续执行
+extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
+    fault_println!("EXCEPTION: BREAKPOINT\n{:#?}", stack_frame);
+}
+
+extern "x86-interrupt" fn divide_error_handler(stack_frame: InterruptStackFrame) {
+    fault_println!("EXCEPTION: DIVIDE ERROR\n{:#?}", stack_frame);
+    crate::hlt_loop();
+}
+
+extern "x86-interrupt" fn invalid_opcode_handler(stack_frame: InterruptStackFrame) {
+    fault_println!("EXCEPTION: INVALID OPCODE\n{:#?}", stack_frame);
+    crate::hlt_loop();
+}
+
+extern "x86-interrupt" fn double_fault_handler(stack_frame: InterruptStackFrame, _error_code: u64) -> ! {
+    fault_println!("EXCEPTION: DOUBLE FAULT\n{:#?}", stack_frame);
+    crate::hlt_loop();
+}
+
+extern "x86-interrupt" fn nmi_handler(stack_frame: InterruptStackFrame) {
+    fault_println!("EXCEPTION: NON-MASKABLE INTERRUPT\n{:#?}", stack_frame);
+}
+
+extern "x86-interrupt" fn general_protection_fault_handler(stack_frame: InterruptStackFrame, error_code: u64) {
+    fault_println!("EXCEPTION: GENERAL PROTECTION FAULT (error code: {:#x})\n{:#?}", error_code, stack_frame);
+    crate::hlt_loop();
+}
+
+extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, error_code: PageFaultErrorCode) {
+    use x86_64::registers::control::Cr2;
+
+    // CR2保存着触发缺页的虚拟地址
+    fault_println!("EXCEPTION: PAGE FAULT");
+    fault_println!("Accessed Address: {:?}", Cr2::read());
+    fault_println!("Error Code: {:?}", error_code);
+    fault_println!("{:#?}", stack_frame);
+    crate::hlt_loop();
+}
+
+// 时钟中断目前只需要应答，否则PIC不会再发送后续中断
+extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    unsafe {
+        PICS.lock().notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
+    }
+}
+
+extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    crate::keyboard::handle_interrupt();
+    unsafe {
+        PICS.lock().notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
+    }
+}
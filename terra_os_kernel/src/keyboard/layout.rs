@@ -0,0 +1,137 @@
+// 键盘布局
+// 每种布局用三张字符表描述主键区字符键：直接按下、按住Shift、按住AltGr
+// 表中依次是扫描码0x02-0x0D(数字行)、0x10-0x1B、0x1E-0x29、0x2B-0x35和0x56(ISO键盘额外的键)，共48个键
+// 死键不做组合，直接输出键上的字符
+
+// 表中表示"该键在此状态下没有字符"
+const NONE: char = '\0';
+
+// 每张表包含的键数
+const KEY_COUNT: usize = 48;
+
+/// 支持的键盘布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// 美式英语
+    Us,
+    /// 英式英语
+    Uk,
+    /// 德语(QWERTZ)
+    De,
+    /// 法语(AZERTY)
+    Fr,
+}
+
+struct Tables {
+    normal: &'static str,
+    shift: &'static str,
+    alt_gr: &'static str,
+}
+
+const US: Tables = Tables {
+    normal: "1234567890-=qwertyuiop[]asdfghjkl;'`\\zxcvbnm,./\\",
+    shift: "!@#$%^&*()_+QWERTYUIOP{}ASDFGHJKL:\"~|ZXCVBNM<>?|",
+    alt_gr: "",
+};
+
+const UK: Tables = Tables {
+    normal: "1234567890-=qwertyuiop[]asdfghjkl;'`#zxcvbnm,./\\",
+    shift: "!\"£$%^&*()_+QWERTYUIOP{}ASDFGHJKL:@¬~ZXCVBNM<>?|",
+    alt_gr: "\0\0\0€\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0¦\0\0\0\0\0\0\0\0\0\0\0\0",
+};
+
+const DE: Tables = Tables {
+    normal: "1234567890ß´qwertzuiopü+asdfghjklöä^#yxcvbnm,.-<",
+    shift: "!\"§$%&/()=?`QWERTZUIOPÜ*ASDFGHJKLÖÄ°'YXCVBNM;:_>",
+    alt_gr: "\0²³\0\0\0{[]}\\\0@\0€\0\0\0\0\0\0\0\0~\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0µ\0\0\0|",
+};
+
+const FR: Tables = Tables {
+    normal: "&é\"'(-è_çà)=azertyuiop^$qsdfghjklmù²*wxcvbn,;:!<",
+    shift: "1234567890°+AZERTYUIOP¨£QSDFGHJKLM%\0µWXCVBN?./§>",
+    alt_gr: "\0~#{[|`\\^@]}\0\0€\0\0\0\0\0\0\0\0¤\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
+};
+
+impl Layout {
+    /// 所有布局，用于列出可选项
+    pub const ALL: [Layout; 4] = [Layout::Us, Layout::Uk, Layout::De, Layout::Fr];
+
+    /// 布局的简称，如"us"
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Us => "us",
+            Layout::Uk => "uk",
+            Layout::De => "de",
+            Layout::Fr => "fr",
+        }
+    }
+
+    /// 按简称查找布局
+    pub fn from_name(name: &str) -> Option<Layout> {
+        Self::ALL.iter().copied().find(|layout| layout.name() == name)
+    }
+
+    fn tables(self) -> &'static Tables {
+        match self {
+            Layout::Us => &US,
+            Layout::Uk => &UK,
+            Layout::De => &DE,
+            Layout::Fr => &FR,
+        }
+    }
+
+    /// 把主键区的make码映射为字符，非字符键返回None
+    /// caps_lock只影响字母，与Shift同时生效时互相抵消
+    pub fn map(self, code: u8, shift: bool, alt_gr: bool, caps_lock: bool) -> Option<char> {
+        if code == 0x39 {
+            return Some(' ');
+        }
+        let index = key_index(code)?;
+        let tables = self.tables();
+
+        if alt_gr {
+            if let Some(c) = lookup(tables.alt_gr, index) {
+                return Some(c);
+            }
+        }
+
+        let normal = lookup(tables.normal, index)?;
+        let c = if shift { lookup(tables.shift, index)? } else { normal };
+        if caps_lock && normal.is_alphabetic() {
+            Some(swap_case(c))
+        } else {
+            Some(c)
+        }
+    }
+}
+
+// 扫描码在字符表中的下标
+fn key_index(code: u8) -> Option<usize> {
+    let index = match code {
+        0x02..=0x0D => code - 0x02,
+        0x10..=0x1B => code - 0x10 + 12,
+        0x1E..=0x29 => code - 0x1E + 24,
+        0x2B..=0x35 => code - 0x2B + 36,
+        0x56 => 47,
+        _ => return None,
+    };
+    debug_assert!((index as usize) < KEY_COUNT);
+    Some(index as usize)
+}
+
+fn lookup(table: &str, index: usize) -> Option<char> {
+    table.chars().nth(index).filter(|&c| c != NONE)
+}
+
+// 切换大小写，转换结果不是单个字符(如ß)时保持不变
+fn swap_case(c: char) -> char {
+    fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+
+    let converted = if c.is_lowercase() { single(c.to_uppercase()) } else { single(c.to_lowercase()) };
+    converted.unwrap_or(c)
+}
@@ -0,0 +1,426 @@
+// PS/2键盘驱动
+// IRQ1中断处理函数把扫描码放入无锁环形缓冲区，终端通过Decoder把扫描码解码为按键事件
+// 扫描码使用第一套(Set 1)：按下为make码，松开为make码|0x80的break码，扩展键带0xE0前缀
+
+mod layout;
+
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+pub use self::layout::Layout;
+
+// 扫描码队列容量，队列满时丢弃新的扫描码
+const QUEUE_SIZE: usize = 128;
+
+/// 单生产者单消费者的无锁扫描码队列
+/// 中断处理函数是唯一的生产者，终端是唯一的消费者
+pub struct ScancodeQueue {
+    buf: [AtomicU8; QUEUE_SIZE],
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+impl ScancodeQueue {
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const EMPTY: AtomicU8 = AtomicU8::new(0);
+        Self {
+            buf: [EMPTY; QUEUE_SIZE],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// 放入一个扫描码，队列已满时返回false
+    pub fn push(&self, scancode: u8) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let next = (tail + 1) % QUEUE_SIZE;
+        if next == self.head.load(Ordering::Acquire) {
+            return false;
+        }
+        self.buf[tail].store(scancode, Ordering::Relaxed);
+        self.tail.store(next, Ordering::Release);
+        true
+    }
+
+    /// 取出一个扫描码，队列为空时返回None
+    pub fn pop(&self) -> Option<u8> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let scancode = self.buf[head].load(Ordering::Relaxed);
+        self.head.store((head + 1) % QUEUE_SIZE, Ordering::Release);
+        Some(scancode)
+    }
+}
+
+static SCANCODES: ScancodeQueue = ScancodeQueue::new();
+
+/// 由键盘中断处理函数调用，从数据端口读取扫描码并入队
+pub fn handle_interrupt() {
+    use x86_64::instructions::port::Port;
+
+    let mut port = Port::new(0x60);
+    let scancode: u8 = unsafe { port.read() };
+    if handle_led_response(scancode) {
+        return;
+    }
+    // 队列满时丢弃，不能在中断上下文中等待
+    let _ = SCANCODES.push(scancode);
+}
+
+/// 阻塞等待下一个扫描码，队列为空时让CPU休眠直到下一次中断
+pub fn next_scancode() -> u8 {
+    use x86_64::instructions::interrupts;
+
+    loop {
+        // 关中断后检查队列，再原子地开中断并休眠，避免检查和休眠之间丢失唤醒
+        interrupts::disable();
+        if let Some(scancode) = SCANCODES.pop() {
+            interrupts::enable();
+            return scancode;
+        }
+        interrupts::enable_and_hlt();
+    }
+}
+
+/// 按键
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// 可打印字符，已按布局、Shift、AltGr和CapsLock转换
+    Char(char),
+    Enter,
+    Backspace,
+    Tab,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    /// 功能键F1-F12
+    F(u8),
+    LeftShift,
+    RightShift,
+    LeftCtrl,
+    RightCtrl,
+    LeftAlt,
+    /// 右Alt，即AltGr
+    RightAlt,
+    CapsLock,
+    NumLock,
+    ScrollLock,
+    /// 尚未识别的按键，保存其make码
+    Unknown(u8),
+}
+
+impl Key {
+    /// 修饰键和锁定键，按下时本身不输入任何内容
+    pub fn is_modifier(self) -> bool {
+        matches!(
+            self,
+            Key::LeftShift | Key::RightShift | Key::LeftCtrl | Key::RightCtrl | Key::LeftAlt | Key::RightAlt
+                | Key::CapsLock | Key::NumLock | Key::ScrollLock
+        )
+    }
+}
+
+/// 按键状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    Down,
+    Up,
+}
+
+/// 修饰键和锁定键的状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub left_shift: bool,
+    pub right_shift: bool,
+    pub left_ctrl: bool,
+    pub right_ctrl: bool,
+    pub left_alt: bool,
+    pub right_alt: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+}
+
+impl Modifiers {
+    pub fn shift(&self) -> bool {
+        self.left_shift || self.right_shift
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.left_ctrl || self.right_ctrl
+    }
+
+    pub fn alt(&self) -> bool {
+        self.left_alt || self.right_alt
+    }
+
+    /// AltGr：右Alt，或者同时按住Ctrl和Alt
+    pub fn alt_gr(&self) -> bool {
+        self.right_alt || (self.ctrl() && self.left_alt)
+    }
+
+    // 键盘LED的状态字节：bit0 ScrollLock，bit1 NumLock，bit2 CapsLock
+    fn leds(&self) -> u8 {
+        (self.scroll_lock as u8) | (self.num_lock as u8) << 1 | (self.caps_lock as u8) << 2
+    }
+}
+
+/// 一次按下或松开事件，附带事件发生时的修饰键状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub state: KeyState,
+    pub modifiers: Modifiers,
+}
+
+/// 扫描码解码器，记录修饰键、锁定键和多字节序列的状态
+/// 按住按键时键盘会重复发送make码，每个make码都产生一次Down事件
+pub struct Decoder {
+    layout: Layout,
+    modifiers: Modifiers,
+    // 上一个字节是0xE0前缀
+    extended: bool,
+    // Pause键的0xE1序列还需跳过的字节数
+    skip: u8,
+    // 正被按住的锁定键，避免按住时的重复make码反复切换状态
+    locks_held: u8,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            layout: Layout::Us,
+            modifiers: Modifiers {
+                left_shift: false,
+                right_shift: false,
+                left_ctrl: false,
+                right_ctrl: false,
+                left_alt: false,
+                right_alt: false,
+                caps_lock: false,
+                num_lock: false,
+                scroll_lock: false,
+            },
+            extended: false,
+            skip: 0,
+            locks_held: 0,
+        }
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    /// 解码一个扫描码，前缀字节和控制器应答不产生事件
+    pub fn decode(&mut self, scancode: u8) -> Option<KeyEvent> {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return None;
+        }
+        match scancode {
+            0xE0 => {
+                self.extended = true;
+                return None;
+            }
+            // Pause键发送E1 1D 45 E1 9D C5，没有松开事件，整体忽略
+            0xE1 => {
+                self.skip = 5;
+                return None;
+            }
+            // 控制器应答(ACK/重发/回显)和错误码
+            0x00 | 0xEE | 0xFA | 0xFE | 0xFF => return None,
+            _ => {}
+        }
+
+        let state = if scancode & 0x80 != 0 { KeyState::Up } else { KeyState::Down };
+        let code = scancode & 0x7F;
+        let key = if core::mem::take(&mut self.extended) {
+            self.decode_extended(code)?
+        } else {
+            self.decode_normal(code)
+        };
+
+        self.update_modifiers(key, state);
+        Some(KeyEvent {
+            key,
+            state,
+            modifiers: self.modifiers,
+        })
+    }
+
+    fn decode_normal(&self, code: u8) -> Key {
+        let m = &self.modifiers;
+        match code {
+            0x01 => Key::Escape,
+            0x0E => Key::Backspace,
+            0x0F => Key::Tab,
+            0x1C => Key::Enter,
+            0x1D => Key::LeftCtrl,
+            0x2A => Key::LeftShift,
+            0x36 => Key::RightShift,
+            0x37 => Key::Char('*'),
+            0x38 => Key::LeftAlt,
+            0x3A => Key::CapsLock,
+            0x3B..=0x44 => Key::F(code - 0x3A),
+            0x45 => Key::NumLock,
+            0x46 => Key::ScrollLock,
+            // 小键盘：NumLock打开时输入数字，否则作为方向键和编辑键
+            0x47..=0x53 if m.num_lock && !m.shift() => Key::Char(b"789-456+1230."[(code - 0x47) as usize] as char),
+            0x47 => Key::Home,
+            0x48 => Key::Up,
+            0x49 => Key::PageUp,
+            0x4A => Key::Char('-'),
+            0x4B => Key::Left,
+            0x4D => Key::Right,
+            0x4E => Key::Char('+'),
+            0x4F => Key::End,
+            0x50 => Key::Down,
+            0x51 => Key::PageDown,
+            0x52 => Key::Insert,
+            0x53 => Key::Delete,
+            0x57 => Key::F(11),
+            0x58 => Key::F(12),
+            _ => match self.layout.map(code, m.shift(), m.alt_gr(), m.caps_lock) {
+                Some(c) => Key::Char(c),
+                None => Key::Unknown(code),
+            },
+        }
+    }
+
+    // E0前缀的扩展键，返回None表示忽略(如PrintScreen附带的假Shift)
+    fn decode_extended(&self, code: u8) -> Option<Key> {
+        let key = match code {
+            0x1C => Key::Enter,
+            0x1D => Key::RightCtrl,
+            0x2A | 0x36 => return None,
+            0x35 => Key::Char('/'),
+            0x38 => Key::RightAlt,
+            0x47 => Key::Home,
+            0x48 => Key::Up,
+            0x49 => Key::PageUp,
+            0x4B => Key::Left,
+            0x4D => Key::Right,
+            0x4F => Key::End,
+            0x50 => Key::Down,
+            0x51 => Key::PageDown,
+            0x52 => Key::Insert,
+            0x53 => Key::Delete,
+            _ => Key::Unknown(code),
+        };
+        Some(key)
+    }
+
+    fn update_modifiers(&mut self, key: Key, state: KeyState) {
+        let down = state == KeyState::Down;
+        let m = &mut self.modifiers;
+        let lock_bit = match key {
+            Key::LeftShift => { m.left_shift = down; return; }
+            Key::RightShift => { m.right_shift = down; return; }
+            Key::LeftCtrl => { m.left_ctrl = down; return; }
+            Key::RightCtrl => { m.right_ctrl = down; return; }
+            Key::LeftAlt => { m.left_alt = down; return; }
+            Key::RightAlt => { m.right_alt = down; return; }
+            Key::CapsLock => 1 << 0,
+            Key::NumLock => 1 << 1,
+            Key::ScrollLock => 1 << 2,
+            _ => return,
+        };
+
+        if !down {
+            self.locks_held &= !lock_bit;
+            return;
+        }
+        if self.locks_held & lock_bit != 0 {
+            return;
+        }
+        self.locks_held |= lock_bit;
+        match key {
+            Key::CapsLock => m.caps_lock = !m.caps_lock,
+            Key::NumLock => m.num_lock = !m.num_lock,
+            _ => m.scroll_lock = !m.scroll_lock,
+        }
+        set_leds(m.leds());
+    }
+}
+
+// 向键盘发送一个字节，先等待控制器输入缓冲区清空
+fn write_keyboard(byte: u8) {
+    use x86_64::instructions::port::Port;
+
+    let mut status: Port<u8> = Port::new(0x64);
+    let mut data: Port<u8> = Port::new(0x60);
+    for _ in 0..100_000 {
+        if unsafe { status.read() } & 0x02 == 0 {
+            break;
+        }
+    }
+    unsafe { data.write(byte) };
+}
+
+// LED命令(0xED)的进度，命令字节和LED字节都要等键盘应答0xFA后才能发送下一个
+const LED_IDLE: u8 = 0;
+const LED_AWAIT_COMMAND_ACK: u8 = 1;
+const LED_AWAIT_DATA_ACK: u8 = 2;
+
+static LED_STATE: AtomicU8 = AtomicU8::new(LED_IDLE);
+// 最新的LED状态字节
+static LED_BYTE: AtomicU8 = AtomicU8::new(0);
+// 已经发给键盘的LED状态字节
+static LED_SENT: AtomicU8 = AtomicU8::new(0);
+
+/// 设置键盘LED：只发送0xED命令，LED字节在中断中收到应答后再发送
+fn set_leds(leds: u8) {
+    use x86_64::instructions::interrupts;
+
+    // 和中断处理函数修改同一组状态，关中断保证检查和发送不被打断
+    interrupts::without_interrupts(|| {
+        LED_BYTE.store(leds, Ordering::Relaxed);
+        // 应答在几毫秒内就会到达，下一次按锁定键时命令还没完成说明应答丢了
+        // (有的键盘和模拟器根本不应答)，此时从头重发，避免状态机永远等下去
+        LED_STATE.store(LED_AWAIT_COMMAND_ACK, Ordering::Relaxed);
+        write_keyboard(0xED);
+    });
+}
+
+// 在中断中推进LED命令，返回true表示这个字节是命令的应答，不应作为扫描码入队
+fn handle_led_response(byte: u8) -> bool {
+    match (LED_STATE.load(Ordering::Relaxed), byte) {
+        (LED_AWAIT_COMMAND_ACK, 0xFA) => {
+            let leds = LED_BYTE.load(Ordering::Relaxed);
+            LED_SENT.store(leds, Ordering::Relaxed);
+            LED_STATE.store(LED_AWAIT_DATA_ACK, Ordering::Relaxed);
+            write_keyboard(leds);
+        }
+        (LED_AWAIT_COMMAND_ACK, 0xFE) => write_keyboard(0xED),
+        (LED_AWAIT_DATA_ACK, 0xFA) => {
+            // 等待应答期间LED又变了，重新发送命令
+            if LED_BYTE.load(Ordering::Relaxed) != LED_SENT.load(Ordering::Relaxed) {
+                LED_STATE.store(LED_AWAIT_COMMAND_ACK, Ordering::Relaxed);
+                write_keyboard(0xED);
+            } else {
+                LED_STATE.store(LED_IDLE, Ordering::Relaxed);
+            }
+        }
+        (LED_AWAIT_DATA_ACK, 0xFE) => write_keyboard(LED_SENT.load(Ordering::Relaxed)),
+        _ => return false,
+    }
+    true
+}
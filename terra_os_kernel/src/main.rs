@@ -8,10 +8,15 @@
 
 extern crate alloc;
 
+use bootloader::{entry_point, BootInfo};
 use core::panic::PanicInfo;
 
 mod allocator;
 mod fs;
+mod gdt;
+mod interrupts;
+mod keyboard;
+mod memory;
 mod system_monitor;
 mod terminal;
 mod vga_buffer;
@@ -19,28 +24,75 @@ mod vga_buffer;
 #[global_allocator]
 static ALLOCATOR: allocator::LinkedListAllocator = allocator::LinkedListAllocator::new();
 
-#[no_mangle]
-pub extern "C" fn _start() -> ! {
+// bootloader检查入口函数的签名，并传入内存映射等启动信息
+entry_point!(kernel_main);
+
+fn kernel_main(boot_info: &'static BootInfo) -> ! {
+    // 加载内核自己的GDT/TSS，再加载IDT，CPU异常不再导致三重错误
+    gdt::init();
+    interrupts::init_idt();
+
+    // 映射并初始化堆，终端和文件系统都要分配内存，所以必须最先完成
+    let physical_memory_offset = x86_64::VirtAddr::new(boot_info.physical_memory_offset);
+    unsafe {
+        memory::init(physical_memory_offset);
+        memory::init_frames(&boot_info.memory_map, physical_memory_offset).expect("no usable memory for the frame allocator");
+    }
+    allocator::init_heap(&ALLOCATOR).expect("heap initialization failed");
+
+    #[cfg(test)]
+    test_main();
+
     println!("TerraOS - A minimal OS with real filesystem!");
     println!("Kernel started successfully!");
+
+    // 初始化PIC并开中断，键盘输入改由IRQ1驱动
+    interrupts::init_pics();
+    x86_64::instructions::interrupts::enable();
+
+    // 初始化VFS并挂载根文件系统，终端和所有命令共享这一个实例
+    match fs::init(&ALLOCATOR) {
+        Ok(()) => println!("Root filesystem mounted"),
+        Err(e) => println!("Failed to mount root filesystem: {}", e),
+    }
     
+    // 监控命令由监控模块注册，终端启动后才能用
+    system_monitor::register_commands();
+
     // 启用终端初始化，传递分配器实例
     terminal::init(&ALLOCATOR);
     
     // This function should not return
-    loop {}
+    hlt_loop()
+}
+
+/// 停机循环，在没有中断时让CPU休眠而不是空转
+pub fn hlt_loop() -> ! {
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+#[cfg(test)]
+fn test_runner(tests: &[&dyn Fn()]) {
+    println!("Running {} tests", tests.len());
+    for test in tests {
+        test();
+    }
+    println!("All tests passed");
+    hlt_loop();
 }
 
 #[panic_handler]
 fn panic(_info: &PanicInfo) -> ! {
-    println!("\n*** KERNEL PANIC ***");
-    println!("System halted due to panic");
-    loop {}
+    fault_println!("\n*** KERNEL PANIC ***");
+    fault_println!("System halted due to panic");
+    hlt_loop()
 }
 
 #[alloc_error_handler]
 fn alloc_error_handler(layout: alloc::alloc::Layout) -> ! {
-    println!("\n*** ALLOCATION ERROR ***");
-    println!("Memory allocation failed: {:?}", layout);
-    loop {}
+    fault_println!("\n*** ALLOCATION ERROR ***");
+    fault_println!("Memory allocation failed: {:?}", layout);
+    hlt_loop()
 }
\ No newline at end of file
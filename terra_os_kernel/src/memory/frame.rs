@@ -0,0 +1,213 @@
+// 物理帧分配器
+// 位图的每一位对应一个4KiB物理帧，位图本身放在第一个足够大的可用区域的开头，
+// 通过bootloader映射的物理内存访问。2MiB大帧由按2MiB对齐的512个连续4KiB帧组成
+
+use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use core::ops::Range;
+use x86_64::structures::paging::{FrameAllocator, FrameDeallocator, PageSize, PhysFrame, Size2MiB, Size4KiB};
+use x86_64::{PhysAddr, VirtAddr};
+
+// 位图中每个字对应的帧数
+const FRAMES_PER_WORD: usize = u64::BITS as usize;
+// 一个2MiB大帧包含的4KiB帧数
+const FRAMES_PER_HUGE_FRAME: usize = (Size2MiB::SIZE / Size4KiB::SIZE) as usize;
+// 一个2MiB大帧在位图中占用的字数
+const WORDS_PER_HUGE_FRAME: usize = FRAMES_PER_HUGE_FRAME / FRAMES_PER_WORD;
+
+/// 内存区域的分类，区域统计按这个顺序排列
+pub const REGION_KINDS: [&str; 8] = ["usable", "kernel", "page tables", "bootloader", "acpi", "reserved", "bad", "other"];
+
+// 固件和设备保留的区域不是内存，不计入安装的内存
+const RESERVED_KIND: usize = 5;
+
+// 内存映射中的区域类型在REGION_KINDS中的下标
+fn region_kind(region_type: MemoryRegionType) -> usize {
+    match region_type {
+        MemoryRegionType::Usable => 0,
+        MemoryRegionType::Kernel | MemoryRegionType::KernelStack => 1,
+        MemoryRegionType::PageTable => 2,
+        MemoryRegionType::Bootloader
+        | MemoryRegionType::BootInfo
+        | MemoryRegionType::Package
+        | MemoryRegionType::FrameZero => 3,
+        MemoryRegionType::AcpiReclaimable | MemoryRegionType::AcpiNvs => 4,
+        MemoryRegionType::Reserved => RESERVED_KIND,
+        MemoryRegionType::BadMemory => 6,
+        _ => 7,
+    }
+}
+
+/// 物理内存统计
+#[derive(Debug, Clone, Copy)]
+pub struct FrameStats {
+    /// 安装的内存字节数：除保留区域以外所有区域的大小
+    pub installed: u64,
+    /// 分配器管理的4KiB帧数，即可用区域中的帧数
+    pub total_frames: u64,
+    /// 空闲的4KiB帧数
+    pub free_frames: u64,
+    /// 可以整块分配的2MiB大帧数
+    pub free_huge_frames: u64,
+    /// 按REGION_KINDS分类的区域字节数
+    pub regions: [u64; REGION_KINDS.len()],
+}
+
+/// 位图物理帧分配器，分配4KiB帧和2MiB大帧
+pub struct BitmapFrameAllocator {
+    // 1表示帧已占用或不可用
+    bitmap: &'static mut [u64],
+    // 从这个字开始查找空闲的4KiB帧
+    next: usize,
+    total_frames: u64,
+    free_frames: u64,
+    installed: u64,
+    regions: [u64; REGION_KINDS.len()],
+}
+
+impl BitmapFrameAllocator {
+    /// 用bootloader的内存映射创建分配器，没有能放下位图的可用区域时返回None
+    ///
+    /// 调用者必须保证内存映射正确，全部物理内存已映射到`physical_memory_offset`，
+    /// 并且只创建一个分配器
+    pub unsafe fn new(memory_map: &'static MemoryMap, physical_memory_offset: VirtAddr) -> Option<Self> {
+        let usable = || memory_map.iter().filter(|region| region.region_type == MemoryRegionType::Usable);
+
+        // 位图覆盖到最高的可用帧，并按大帧对齐，这样每个大帧都能整块检查
+        let frame_count = usable().map(|region| region.range.end_frame_number).max()? as usize;
+        let words = align_up(frame_count, FRAMES_PER_HUGE_FRAME) / FRAMES_PER_WORD;
+        let bitmap_size = (words * core::mem::size_of::<u64>()) as u64;
+
+        let bitmap_start = usable()
+            .find(|region| region.range.end_addr() - region.range.start_addr() >= bitmap_size)?
+            .range
+            .start_addr();
+        let bitmap_ptr = (physical_memory_offset + bitmap_start).as_mut_ptr::<u64>();
+        let bitmap = core::slice::from_raw_parts_mut(bitmap_ptr, words);
+        bitmap.fill(u64::MAX);
+
+        let mut allocator = BitmapFrameAllocator {
+            bitmap,
+            next: 0,
+            total_frames: 0,
+            free_frames: 0,
+            installed: 0,
+            regions: [0; REGION_KINDS.len()],
+        };
+        for region in memory_map.iter() {
+            let size = region.range.end_addr() - region.range.start_addr();
+            let kind = region_kind(region.region_type);
+            allocator.regions[kind] += size;
+            if kind != RESERVED_KIND {
+                allocator.installed += size;
+            }
+            if region.region_type == MemoryRegionType::Usable {
+                let frames = region.range.start_frame_number as usize..region.range.end_frame_number as usize;
+                allocator.total_frames += frames.len() as u64;
+                allocator.mark(frames, false);
+            }
+        }
+
+        // 位图自己占用的帧
+        let first = (bitmap_start / Size4KiB::SIZE) as usize;
+        let bitmap_frames = align_up(bitmap_size as usize, Size4KiB::SIZE as usize) / Size4KiB::SIZE as usize;
+        allocator.mark(first..first + bitmap_frames, true);
+        Some(allocator)
+    }
+
+    /// 分配一个4KiB帧
+    pub fn allocate_4k(&mut self) -> Option<PhysFrame<Size4KiB>> {
+        let words = self.bitmap.len();
+        let word = (0..words).map(|i| (self.next + i) % words).find(|&word| self.bitmap[word] != u64::MAX)?;
+        let bit = self.bitmap[word].trailing_ones() as usize;
+        self.bitmap[word] |= 1 << bit;
+        self.free_frames -= 1;
+        self.next = word;
+        Some(frame_at(word * FRAMES_PER_WORD + bit))
+    }
+
+    /// 分配一个2MiB大帧，需要512个按2MiB对齐的连续空闲帧
+    pub fn allocate_2m(&mut self) -> Option<PhysFrame<Size2MiB>> {
+        let huge = self
+            .bitmap
+            .chunks_exact(WORDS_PER_HUGE_FRAME)
+            .position(|words| words.iter().all(|&word| word == 0))?;
+        let first = huge * FRAMES_PER_HUGE_FRAME;
+        self.mark(first..first + FRAMES_PER_HUGE_FRAME, true);
+        Some(frame_at(first))
+    }
+
+    /// 当前的物理内存统计
+    pub fn stats(&self) -> FrameStats {
+        let free_huge_frames = self
+            .bitmap
+            .chunks_exact(WORDS_PER_HUGE_FRAME)
+            .filter(|words| words.iter().all(|&word| word == 0))
+            .count();
+        FrameStats {
+            installed: self.installed,
+            total_frames: self.total_frames,
+            free_frames: self.free_frames,
+            free_huge_frames: free_huge_frames as u64,
+            regions: self.regions,
+        }
+    }
+
+    // 释放一个任意大小的帧，帧必须是从本分配器分配的
+    fn free<S: PageSize>(&mut self, frame: PhysFrame<S>) {
+        let first = (frame.start_address().as_u64() / Size4KiB::SIZE) as usize;
+        let count = (S::SIZE / Size4KiB::SIZE) as usize;
+        self.mark(first..first + count, false);
+        self.next = self.next.min(first / FRAMES_PER_WORD);
+    }
+
+    // 把一段帧标记为占用或空闲并更新空闲帧数，超出位图的帧被忽略
+    fn mark(&mut self, frames: Range<usize>, used: bool) {
+        let end = frames.end.min(self.bitmap.len() * FRAMES_PER_WORD);
+        for frame in frames.start..end {
+            let word = &mut self.bitmap[frame / FRAMES_PER_WORD];
+            let bit = 1 << (frame % FRAMES_PER_WORD);
+            if (*word & bit != 0) == used {
+                continue;
+            }
+            *word ^= bit;
+            if used {
+                self.free_frames -= 1;
+            } else {
+                self.free_frames += 1;
+            }
+        }
+    }
+}
+
+unsafe impl FrameAllocator<Size4KiB> for BitmapFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
+        self.allocate_4k()
+    }
+}
+
+unsafe impl FrameAllocator<Size2MiB> for BitmapFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame<Size2MiB>> {
+        self.allocate_2m()
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for BitmapFrameAllocator {
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size4KiB>) {
+        self.free(frame);
+    }
+}
+
+impl FrameDeallocator<Size2MiB> for BitmapFrameAllocator {
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size2MiB>) {
+        self.free(frame);
+    }
+}
+
+// 第index个4KiB帧所在的帧，index必须按S的大小对齐
+fn frame_at<S: PageSize>(index: usize) -> PhysFrame<S> {
+    PhysFrame::containing_address(PhysAddr::new(index as u64 * Size4KiB::SIZE))
+}
+
+fn align_up(value: usize, align: usize) -> usize {
+    value.div_ceil(align) * align
+}
@@ -0,0 +1,88 @@
+// 分页和物理内存
+// bootloader把全部物理内存映射到physical_memory_offset开始的虚拟地址，
+// 通过这个映射可以访问页表本身，并用OffsetPageTable建立新的映射；物理帧由位图帧分配器管理
+
+mod frame;
+
+use bootloader::bootinfo::MemoryMap;
+use spin::{Mutex, Once};
+use x86_64::instructions::interrupts;
+use x86_64::registers::control::Cr3;
+use x86_64::structures::paging::mapper::MapToError;
+use x86_64::structures::paging::{
+    FrameAllocator, FrameDeallocator, Mapper, OffsetPageTable, Page, PageTable, PageTableFlags, Size4KiB,
+};
+use x86_64::VirtAddr;
+
+pub use self::frame::{BitmapFrameAllocator, FrameStats, REGION_KINDS};
+
+// 内核页表，启动时用当前的4级页表创建一次
+static PAGE_TABLE: Once<Mutex<OffsetPageTable<'static>>> = Once::new();
+
+/// 用当前的4级页表创建全局的OffsetPageTable
+///
+/// 调用者必须保证全部物理内存已映射到`physical_memory_offset`，并且只调用一次，
+/// 否则会产生指向同一页表的多个`&mut`引用
+pub unsafe fn init(physical_memory_offset: VirtAddr) {
+    let level_4_table = active_level_4_table(physical_memory_offset);
+    PAGE_TABLE.call_once(|| Mutex::new(OffsetPageTable::new(level_4_table, physical_memory_offset)));
+}
+
+// CR3中记录的4级页表
+unsafe fn active_level_4_table(physical_memory_offset: VirtAddr) -> &'static mut PageTable {
+    let (level_4_table_frame, _) = Cr3::read();
+    let phys = level_4_table_frame.start_address();
+    let virt = physical_memory_offset + phys.as_u64();
+    &mut *virt.as_mut_ptr::<PageTable>()
+}
+
+// 全局物理帧分配器，启动时根据内存映射初始化一次
+static FRAMES: Once<Mutex<BitmapFrameAllocator>> = Once::new();
+
+/// 根据bootloader的内存映射初始化全局物理帧分配器，应在启动时调用一次
+///
+/// 调用者必须保证内存映射正确，并且全部物理内存已映射到`physical_memory_offset`
+pub unsafe fn init_frames(
+    memory_map: &'static MemoryMap,
+    physical_memory_offset: VirtAddr,
+) -> Option<&'static Mutex<BitmapFrameAllocator>> {
+    let allocator = BitmapFrameAllocator::new(memory_map, physical_memory_offset)?;
+    Some(FRAMES.call_once(|| Mutex::new(allocator)))
+}
+
+/// 获取全局物理帧分配器，尚未初始化时返回None
+pub fn frames() -> Option<&'static Mutex<BitmapFrameAllocator>> {
+    FRAMES.r#try()
+}
+
+/// 为一个页分配物理帧并映射为可写，页表或帧分配器尚未初始化时当作没有空闲帧
+///
+/// 持有页表和帧分配器的锁期间不会分配堆内存，所以堆分配器可以在分配过程中调用
+pub fn map_page(page: Page<Size4KiB>) -> Result<(), MapToError<Size4KiB>> {
+    let (page_table, frames) = match (PAGE_TABLE.r#try(), frames()) {
+        (Some(page_table), Some(frames)) => (page_table, frames),
+        _ => return Err(MapToError::FrameAllocationFailed),
+    };
+    // 关中断后加锁，堆增长可能发生在中断处理函数中，不能在被打断代码持有的锁上自旋
+    interrupts::without_interrupts(|| {
+        let mut frames = frames.lock();
+        let frame = frames.allocate_frame().ok_or(MapToError::FrameAllocationFailed)?;
+        let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
+        match unsafe { page_table.lock().map_to(page, frame, flags, &mut *frames) } {
+            Ok(flush) => {
+                flush.flush();
+                Ok(())
+            }
+            Err(e) => {
+                // 映射失败时帧没有被使用，还给分配器
+                unsafe { frames.deallocate_frame(frame) };
+                Err(e)
+            }
+        }
+    })
+}
+
+/// 当前的物理内存统计，帧分配器尚未初始化时返回None
+pub fn frame_stats() -> Option<FrameStats> {
+    frames().map(|frames| interrupts::without_interrupts(|| frames.lock().stats()))
+}
@@ -1,5 +1,34 @@
+use alloc::format;
+use alloc::vec::Vec;
+
 use crate::allocator::{LinkedListAllocator, MemoryStats};
-use crate::terminal::Terminal;
+use crate::memory::{self, FrameStats, REGION_KINDS};
+use crate::terminal::commands::{
+    self, Command, FramesCommand, FreeCommand, MeminfoCommand, MemstatsCommand, SyshealthCommand, SysinfoCommand,
+};
+use crate::terminal::io::Context;
+
+// 物理帧的大小
+const FRAME_SIZE: u64 = 4096;
+
+// 监控命令不是shell内建命令，由监控模块在启动时注册
+const MONITOR_COMMANDS: [&'static dyn Command; 6] = [
+    &MeminfoCommand,
+    &MemstatsCommand,
+    &SysinfoCommand,
+    &SyshealthCommand,
+    &FreeCommand,
+    &FramesCommand,
+];
+
+/// 把监控命令注册到终端的命令表，必须在终端启动前调用
+pub fn register_commands() {
+    for command in MONITOR_COMMANDS {
+        if let Err(e) = commands::register_command(command) {
+            crate::println!("Failed to register '{}': {}", command.name(), e);
+        }
+    }
+}
 
 pub struct SystemMonitor {
     allocator: &'static LinkedListAllocator,
@@ -10,115 +39,206 @@ impl SystemMonitor {
         SystemMonitor { allocator }
     }
 
-    pub fn display_memory_info(&self, terminal: &mut Terminal) {
+    pub fn display_memory_info(&self, out: &mut Context) {
         let stats = self.allocator.get_memory_stats();
         
-        terminal.write_str("=== 内存监控信息 ===\n");
-        terminal.write_str("总堆大小:        ");
-        self.format_bytes(stats.total_heap_size, terminal);
-        terminal.write_str("\n");
-
-        terminal.write_str("当前已分配:      ");
-        self.format_bytes(stats.current_allocated, terminal);
-        terminal.write_str(" (");
-        self.format_percentage((stats.current_allocated as f64 / stats.total_heap_size as f64) * 100.0, terminal);
-        terminal.write_str(")\n");
-
-        terminal.write_str("当前可用:        ");
-        self.format_bytes(stats.free_memory, terminal);
-        terminal.write_str(" (");
-        self.format_percentage((stats.free_memory as f64 / stats.total_heap_size as f64) * 100.0, terminal);
-        terminal.write_str(")\n");
-
-        terminal.write_str("历史最大分配:    ");
-        self.format_bytes(stats.max_allocated, terminal);
-        terminal.write_str(" (");
-        self.format_percentage((stats.max_allocated as f64 / stats.total_heap_size as f64) * 100.0, terminal);
-        terminal.write_str(")\n");
-
-        terminal.write_str("\n=== 分配统计 ===\n");
-        terminal.write_str("总分配次数:     ");
-        self.format_number(stats.allocation_count, terminal);
-        terminal.write_str("\n");
-
-        terminal.write_str("总释放次数:     ");
-        self.format_number(stats.deallocation_count, terminal);
-        terminal.write_str("\n");
-
-        terminal.write_str("总分配内存:     ");
-        self.format_bytes(stats.allocated, terminal);
-        terminal.write_str("\n");
-
-        terminal.write_str("总释放内存:     ");
-        self.format_bytes(stats.freed, terminal);
-        terminal.write_str("\n");
-
-        terminal.write_str("\n=== 性能指标 ===\n");
+        out.write_str("=== 内存监控信息 ===\n");
+        out.write_str("总堆大小:        ");
+        self.format_bytes(stats.total_heap_size, out);
+        out.write_str(" / 上限 ");
+        self.format_bytes(stats.heap_limit, out);
+        out.write_str("\n");
+
+        out.write_str("堆增长次数:      ");
+        self.format_number(stats.growth_count, out);
+        if stats.growth_failures > 0 {
+            out.write_str(" (失败 ");
+            self.format_number(stats.growth_failures, out);
+            out.write_str(")");
+        }
+        out.write_str("\n");
+
+        out.write_str("当前已分配:      ");
+        self.format_bytes(stats.current_allocated, out);
+        out.write_str(" (");
+        self.format_percentage((stats.current_allocated as f64 / stats.total_heap_size as f64) * 100.0, out);
+        out.write_str(")\n");
+
+        out.write_str("当前可用:        ");
+        self.format_bytes(stats.free_memory, out);
+        out.write_str(" (");
+        self.format_percentage((stats.free_memory as f64 / stats.total_heap_size as f64) * 100.0, out);
+        out.write_str(")\n");
+
+        out.write_str("历史最大分配:    ");
+        self.format_bytes(stats.max_allocated, out);
+        out.write_str(" (");
+        self.format_percentage((stats.max_allocated as f64 / stats.total_heap_size as f64) * 100.0, out);
+        out.write_str(")\n");
+
+        out.write_str("\n=== 分配统计 ===\n");
+        out.write_str("总分配次数:     ");
+        self.format_number(stats.allocation_count, out);
+        out.write_str("\n");
+
+        out.write_str("总释放次数:     ");
+        self.format_number(stats.deallocation_count, out);
+        out.write_str("\n");
+
+        out.write_str("总分配内存:     ");
+        self.format_bytes(stats.allocated, out);
+        out.write_str("\n");
+
+        out.write_str("总释放内存:     ");
+        self.format_bytes(stats.freed, out);
+        out.write_str("\n");
+
+        out.write_str("\n=== 性能指标 ===\n");
         
-        if stats.allocation_count > 0 {
-            let avg_alloc_size = stats.allocated / stats.allocation_count;
-            terminal.write_str("平均分配大小:   ");
-            self.format_bytes(avg_alloc_size, terminal);
-            terminal.write_str("\n");
+        if let Some(avg_alloc_size) = stats.allocated.checked_div(stats.allocation_count) {
+            out.write_str("平均分配大小:   ");
+            self.format_bytes(avg_alloc_size, out);
+            out.write_str("\n");
         }
 
-        if stats.deallocation_count > 0 {
-            let avg_free_size = stats.freed / stats.deallocation_count;
-            terminal.write_str("平均释放大小:   ");
-            self.format_bytes(avg_free_size, terminal);
-            terminal.write_str("\n");
+        if let Some(avg_free_size) = stats.freed.checked_div(stats.deallocation_count) {
+            out.write_str("平均释放大小:   ");
+            self.format_bytes(avg_free_size, out);
+            out.write_str("\n");
         }
 
-        terminal.write_str("内存利用率:     ");
-        self.format_percentage((stats.current_allocated as f64 / stats.total_heap_size as f64) * 100.0, terminal);
-        terminal.write_str("\n");
+        out.write_str("内存利用率:     ");
+        self.format_percentage((stats.current_allocated as f64 / stats.total_heap_size as f64) * 100.0, out);
+        out.write_str("\n");
 
-        terminal.write_str("碎片化程度:     ");
-        let fragmentation = if stats.current_allocated > 0 {
-            ((stats.total_heap_size - stats.free_memory) as f64 / stats.total_heap_size as f64) * 100.0
-        } else {
-            0.0
-        };
-        self.format_percentage(fragmentation, terminal);
-        terminal.write_str("\n");
+        out.write_str("碎片化程度:     ");
+        self.format_percentage(fragmentation(&stats), out);
+        out.write_str("\n");
+
+        out.write_str("空闲块数:       ");
+        self.format_number(stats.free_blocks, out);
+        out.write_str("\n");
+
+        out.write_str("最大空闲块:     ");
+        self.format_bytes(stats.largest_free_block, out);
+        out.write_str("\n");
+    }
+
+    /// 各类别slab缓存的命中率和缓存的块数
+    pub fn display_slab_stats(&self, out: &mut Context) {
+        out.write_str("=== Slab缓存 ===\n");
+        out.write_str(&format!("{:>8}{:>10}{:>10}{:>9}{:>8}\n", "块大小", "命中", "未命中", "命中率", "缓存"));
+        for slab in self.allocator.get_slab_stats() {
+            let requests = slab.hits + slab.misses;
+            let hit_rate = if requests > 0 { slab.hits as f64 / requests as f64 * 100.0 } else { 0.0 };
+            out.write_str(&format!(
+                "{:>8}{:>10}{:>10}{:>8.1}%{:>8}\n",
+                slab.block_size, slab.hits, slab.misses, hit_rate, slab.cached
+            ));
+        }
     }
 
-    pub fn display_system_info(&self, terminal: &mut Terminal) {
-        terminal.write_str("=== 系统信息 ===\n");
-        terminal.write_str("操作系统:       TerraOS (Rust Kernel)\n");
-        terminal.write_str("内核版本:       0.1.0\n");
-        terminal.write_str("构建时间:       运行时统计\n");
-        terminal.write_str("架构:           x86_64\n");
+    pub fn display_system_info(&self, out: &mut Context) {
+        out.write_str("=== 系统信息 ===\n");
+        out.write_str("操作系统:       TerraOS (Rust Kernel)\n");
+        out.write_str("内核版本:       0.1.0\n");
+        out.write_str("构建时间:       运行时统计\n");
+        out.write_str("架构:           x86_64\n");
         
-        terminal.write_str("\n=== 系统状态 ===\n");
-        terminal.write_str("系统状态:       正常运行\n");
-        terminal.write_str("终端:           已初始化\n");
-        terminal.write_str("内存管理:       已启用\n");
-        terminal.write_str("VGA缓冲:        双缓冲模式\n");
+        out.write_str("\n=== 系统状态 ===\n");
+        out.write_str("系统状态:       正常运行\n");
+        out.write_str("终端:           已初始化\n");
+        out.write_str("内存管理:       已启用\n");
+        out.write_str("VGA缓冲:        双缓冲模式\n");
+
+        out.write_str("\n=== 物理内存 ===\n");
+        match self.get_frame_stats() {
+            Some(frames) => {
+                let free = frames.free_frames * FRAME_SIZE;
+                out.write_str("已安装内存:     ");
+                self.format_bytes(frames.installed, out);
+                out.write_str("\n");
+
+                out.write_str("可用内存:       ");
+                self.format_bytes(free, out);
+                out.write_str(" (");
+                self.format_percentage(free as f64 / frames.installed as f64 * 100.0, out);
+                out.write_str(")\n");
+            }
+            None => out.write_str("物理内存:       未初始化\n"),
+        }
+    }
+
+    /// 物理内存和堆的总计、已用和可用大小，单位KB
+    pub fn display_free(&self, out: &mut Context) {
+        out.write_str(&format!("{:<8}{:>12}{:>12}{:>12}\n", "", "总计", "已用", "可用"));
+        if let Some(frames) = self.get_frame_stats() {
+            let total = frames.total_frames * FRAME_SIZE;
+            let free = frames.free_frames * FRAME_SIZE;
+            out.write_str(&format!("{:<8}{:>12}{:>12}{:>12}\n", "内存:", total / 1024, (total - free) / 1024, free / 1024));
+        }
+        let heap = self.get_memory_stats();
+        out.write_str(&format!(
+            "{:<8}{:>12}{:>12}{:>12}\n",
+            "堆:",
+            heap.total_heap_size / 1024,
+            heap.current_allocated / 1024,
+            heap.free_memory / 1024,
+        ));
+    }
+
+    /// 内存映射中各类区域的大小，以及物理帧分配器的帧数
+    pub fn display_frames(&self, out: &mut Context) {
+        let frames = match self.get_frame_stats() {
+            Some(frames) => frames,
+            None => {
+                out.write_str("物理帧分配器未初始化\n");
+                return;
+            }
+        };
+
+        out.write_str("=== 内存区域 ===\n");
+        for (kind, &bytes) in REGION_KINDS.iter().zip(frames.regions.iter()) {
+            if bytes > 0 {
+                out.write_str(&format!("{:<16}", kind));
+                self.format_bytes(bytes, out);
+                out.write_str("\n");
+            }
+        }
+
+        out.write_str("\n=== 物理帧 ===\n");
+        out.write_str(&format!("4KiB帧总数:     {}\n", frames.total_frames));
+        out.write_str(&format!("已分配:         {}\n", frames.total_frames - frames.free_frames));
+        out.write_str(&format!("空闲:           {}\n", frames.free_frames));
+        out.write_str(&format!("空闲2MiB大帧:   {}\n", frames.free_huge_frames));
     }
 
-    fn format_bytes(&self, bytes: u64, terminal: &mut Terminal) {
+    fn format_bytes(&self, bytes: u64, out: &mut Context) {
         if bytes < 1024 {
-            terminal.write_str(&format!("{} B", bytes));
+            out.write_str(&format!("{} B", bytes));
         } else if bytes < 1024 * 1024 {
-            terminal.write_str(&format!("{}.{} KB", bytes / 1024, (bytes % 1024) * 10 / 1024));
+            out.write_str(&format!("{}.{} KB", bytes / 1024, (bytes % 1024) * 10 / 1024));
         } else {
-            terminal.write_str(&format!("{}.{} MB", bytes / (1024 * 1024), (bytes % (1024 * 1024)) * 10 / (1024 * 1024)));
+            out.write_str(&format!("{}.{} MB", bytes / (1024 * 1024), (bytes % (1024 * 1024)) * 10 / (1024 * 1024)));
         }
     }
 
-    fn format_percentage(&self, percentage: f64, terminal: &mut Terminal) {
-        terminal.write_str(&format!("{:.1}%", percentage));
+    fn format_percentage(&self, percentage: f64, out: &mut Context) {
+        out.write_str(&format!("{:.1}%", percentage));
     }
 
-    fn format_number(&self, number: u64, terminal: &mut Terminal) {
-        terminal.write_str(&format!("{}", number));
+    fn format_number(&self, number: u64, out: &mut Context) {
+        out.write_str(&format!("{}", number));
     }
 
     pub fn get_memory_stats(&self) -> MemoryStats {
         self.allocator.get_memory_stats()
     }
 
+    pub fn get_frame_stats(&self) -> Option<FrameStats> {
+        memory::frame_stats()
+    }
+
     pub fn check_memory_health(&self) -> MemoryHealth {
         let stats = self.get_memory_stats();
         let usage_percent = (stats.current_allocated as f64 / stats.total_heap_size as f64) * 100.0;
@@ -140,11 +260,7 @@ impl SystemMonitor {
         }
 
         // 检查碎片化
-        let fragmentation = if stats.current_allocated > 0 {
-            ((stats.total_heap_size - stats.free_memory) as f64 / stats.total_heap_size as f64) * 100.0
-        } else {
-            0.0
-        };
+        let fragmentation = fragmentation(&stats);
 
         if fragmentation > 80.0 {
             warnings.push("内存碎片化严重");
@@ -152,7 +268,7 @@ impl SystemMonitor {
         }
 
         // 检查分配失败
-        if stats.total_allocated == 0 && stats.allocation_count > 0 {
+        if stats.allocated == 0 && stats.allocation_count > 0 {
             status = MemoryHealthStatus::Error;
             warnings.push("检测到分配异常");
         }
@@ -167,61 +283,69 @@ impl SystemMonitor {
         }
     }
 
-    pub fn display_health_check(&self, terminal: &mut Terminal) {
+    pub fn display_health_check(&self, out: &mut Context) {
         let health = self.check_memory_health();
         
-        terminal.write_str("=== 内存健康检查 ===\n");
-        terminal.write_str("健康状态:       ");
+        out.write_str("=== 内存健康检查 ===\n");
+        out.write_str("健康状态:       ");
         
         match health.status {
             MemoryHealthStatus::Healthy => {
-                terminal.write_str("正常\n");
-                terminal.write_str("✅ 系统内存状态良好\n");
+                out.write_str("正常\n");
+                out.write_str("✅ 系统内存状态良好\n");
             }
             MemoryHealthStatus::Warning => {
-                terminal.write_str("警告\n");
-                terminal.write_str("⚠️  内存使用率较高，需要关注\n");
+                out.write_str("警告\n");
+                out.write_str("⚠️  内存使用率较高，需要关注\n");
             }
             MemoryHealthStatus::Critical => {
-                terminal.write_str("严重\n");
-                terminal.write_str("🚨 内存使用率过高！\n");
+                out.write_str("严重\n");
+                out.write_str("🚨 内存使用率过高！\n");
             }
             MemoryHealthStatus::Error => {
-                terminal.write_str("错误\n");
-                terminal.write_str("❌ 检测到内存分配异常\n");
+                out.write_str("错误\n");
+                out.write_str("❌ 检测到内存分配异常\n");
             }
         }
 
         if !health.warnings.is_empty() {
-            terminal.write_str("\n⚠️  警告信息:\n");
+            out.write_str("\n⚠️  警告信息:\n");
             for warning in health.warnings {
-                terminal.write_str("• ");
-                terminal.write_str(warning);
-                terminal.write_str("\n");
+                out.write_str("• ");
+                out.write_str(warning);
+                out.write_str("\n");
             }
         }
 
         if !health.recommendations.is_empty() {
-            terminal.write_str("\n💡 建议:\n");
+            out.write_str("\n💡 建议:\n");
             for recommendation in health.recommendations {
-                terminal.write_str("• ");
-                terminal.write_str(recommendation);
-                terminal.write_str("\n");
+                out.write_str("• ");
+                out.write_str(recommendation);
+                out.write_str("\n");
             }
         }
 
-        terminal.write_str("\n内存使用率:     ");
-        self.format_percentage(health.usage_percent, terminal);
-        terminal.write_str("\n");
+        out.write_str("\n内存使用率:     ");
+        self.format_percentage(health.usage_percent, out);
+        out.write_str("\n");
+
+        out.write_str("可用内存比例:   ");
+        self.format_percentage(health.free_percent, out);
+        out.write_str("\n");
 
-        terminal.write_str("可用内存比例:   ");
-        self.format_percentage(health.free_percent, terminal);
-        terminal.write_str("\n");
+        out.write_str("碎片化程度:     ");
+        self.format_percentage(health.fragmentation, out);
+        out.write_str("\n");
+    }
+}
 
-        terminal.write_str("碎片化程度:     ");
-        self.format_percentage(health.fragmentation, terminal);
-        terminal.write_str("\n");
+// 碎片化程度：空闲内存中不属于最大空闲块的比例，即无法用于一次大分配的部分
+fn fragmentation(stats: &MemoryStats) -> f64 {
+    if stats.free_memory == 0 {
+        return 0.0;
     }
+    (1.0 - stats.largest_free_block as f64 / stats.free_memory as f64) * 100.0
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -1,12 +1,25 @@
 //! Command implementations for the TerraOS terminal
 
-use crate::terminal::Terminal;
+use crate::terminal::interpreter::{self, Flow};
+use crate::terminal::{env, parser};
+use crate::terminal::io::Context;
 use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use spin::RwLock;
+
+use crate::fs::file::{OpenFlags, SeekFrom};
+use crate::fs::VfsInode;
+use crate::system_monitor::SystemMonitor;
 
 /// A trait for terminal commands
-pub trait Command {
-    /// Execute the command
-    fn execute(&self, terminal: &mut Terminal, args: &[&str]);
+///
+/// Commands are shared through the global registry, so they must be `Sync`.
+pub trait Command: Sync {
+    /// Execute the command; `argv[0]` is the command name, followed by its arguments
+    ///
+    /// Returns the exit status: 0 on success, 1 on failure and 2 for invalid usage.
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32;
 
     /// Get the command name
     fn name(&self) -> &str;
@@ -19,13 +32,15 @@ pub trait Command {
 pub struct HelpCommand;
 
 impl Command for HelpCommand {
-    fn execute(&self, terminal: &mut Terminal, _args: &[&str]) {
-        terminal.write_str("Available commands:\n");
-        terminal.write_str("  help   - Display this help message\n");
-        terminal.write_str("  clear  - Clear the terminal screen\n");
-        terminal.write_str("  echo   - Print a message\n");
-        terminal.write_str("  ls     - List files and directories\n");
-        terminal.write_str("  cat    - Display the content of a file\n");
+    fn execute(&self, ctx: &mut Context, _argv: &[&str]) -> i32 {
+        let commands = get_commands();
+        let width = commands.iter().map(|cmd| cmd.name().len()).max().unwrap_or(0);
+
+        ctx.write_str("Available commands:\n");
+        for cmd in commands {
+            ctx.write_str(&format!("  {:<width$} - {}\n", cmd.name(), cmd.description(), width = width));
+        }
+        0
     }
 
     fn name(&self) -> &str {
@@ -33,7 +48,7 @@ impl Command for HelpCommand {
     }
 
     fn description(&self) -> &str {
-        "Display help information"
+        "Display this help message"
     }
 }
 
@@ -41,8 +56,9 @@ impl Command for HelpCommand {
 pub struct ClearCommand;
 
 impl Command for ClearCommand {
-    fn execute(&self, terminal: &mut Terminal, _args: &[&str]) {
-        terminal.clear();
+    fn execute(&self, ctx: &mut Context, _argv: &[&str]) -> i32 {
+        ctx.terminal.clear();
+        0
     }
 
     fn name(&self) -> &str {
@@ -58,18 +74,19 @@ impl Command for ClearCommand {
 pub struct EchoCommand;
 
 impl Command for EchoCommand {
-    fn execute(&self, terminal: &mut Terminal, args: &[&str]) {
-        if args.is_empty() {
-            terminal.write_byte(b'\n');
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        if argv.len() < 2 {
+            ctx.write_byte(b'\n');
         } else {
-            for (i, arg) in args.iter().enumerate() {
+            for (i, arg) in argv[1..].iter().enumerate() {
                 if i > 0 {
-                    terminal.write_byte(b' ');
+                    ctx.write_byte(b' ');
                 }
-                terminal.write_str(arg);
+                ctx.write_str(arg);
             }
-            terminal.write_byte(b'\n');
+            ctx.write_byte(b'\n');
         }
+        0
     }
 
     fn name(&self) -> &str {
@@ -85,37 +102,52 @@ impl Command for EchoCommand {
 pub struct LsCommand;
 
 impl Command for LsCommand {
-    fn execute(&self, terminal: &mut Terminal, args: &[&str]) {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
         // Parse options
         let mut long_format = false;
         let mut show_all = false;
+        let mut show_inode = false;
+        let mut target = ".";
         
-        for arg in args {
+        for arg in &argv[1..] {
             match *arg {
                 "-l" => long_format = true,
                 "-a" => show_all = true,
-                _ => {}
+                "-i" => show_inode = true,
+                path => target = path,
             }
         }
         
-        use crate::fs::new_fs::{SimpleFileSystem, MemoryBlockDevice};
-        let device = MemoryBlockDevice::new();
-        let mut fs = SimpleFileSystem::new(device);
-        fs.init();
-
-        // For now, we'll just print a message indicating the command is being implemented
-        if long_format && show_all {
-            terminal.write_str("Listing all files and directories with details...\n");
-        } else if long_format {
-            terminal.write_str("Listing files and directories with details...\n");
-        } else if show_all {
-            terminal.write_str("Listing all files and directories...\n");
-        } else {
-            terminal.write_str("Listing files and directories...\n");
+        let fs = match ctx.terminal.vfs() {
+            Some(fs) => fs,
+            None => return 1,
+        };
+
+        match fs.read_dir(&ctx.terminal.resolve_path(target)) {
+            Ok(entries) => {
+                for entry in entries {
+                    if !show_all && entry.name.starts_with('.') {
+                        continue;
+                    }
+                    if show_inode {
+                        ctx.write_str(&format!("{:>6} ", entry.inode.ino));
+                    }
+
+                    if long_format {
+                        let type_char = if entry.is_dir { 'd' } else { '-' };
+                        let size_str = ctx.terminal.format_size(entry.size);
+                        ctx.write_str(&format!("{} {} {} {}\n", type_char, "rwxr-xr-x", size_str, entry.name));
+                    } else {
+                        ctx.write_str(&format!("{}\n", entry.name));
+                    }
+                }
+                0
+            },
+            Err(e) => {
+                ctx.print_fs_error("ls", &format!("cannot access '{}'", target), e);
+                1
+            }
         }
-        
-        // TODO: Implement actual directory listing
-        terminal.write_str("Directory listing functionality is not yet fully implemented.\n");
     }
 
     fn name(&self) -> &str {
@@ -130,9 +162,56 @@ impl Command for LsCommand {
 /// The cat command
 pub struct CatCommand;
 
+// Number of bytes cat reads from a file at a time
+const CAT_CHUNK_SIZE: u64 = 512;
+
 impl Command for CatCommand {
-    fn execute(&self, terminal: &mut Terminal, _args: &[&str]) {
-        terminal.write_str("cat command is not yet implemented for the new filesystem.\n");
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        // Without file arguments, copy the input to the output
+        if argv.len() < 2 {
+            if ctx.stdin_is_terminal() {
+                ctx.write_err("Usage: cat <file>...\n");
+                return 2;
+            }
+            loop {
+                match ctx.read(CAT_CHUNK_SIZE as usize) {
+                    Ok(data) if data.is_empty() => return 0,
+                    Ok(data) => ctx.write_bytes(&data),
+                    Err(e) => {
+                        ctx.print_fs_error("cat", "stdin", e);
+                        return 1;
+                    }
+                }
+            }
+        }
+
+        let mut status = 0;
+        for name in &argv[1..] {
+            let path = ctx.terminal.resolve_path(name);
+            let fd = match ctx.terminal.files().open(&path, OpenFlags::READ) {
+                Ok(fd) => fd,
+                Err(e) => {
+                    ctx.print_fs_error("cat", name, e);
+                    status = 1;
+                    continue;
+                }
+            };
+
+            // Stream the file in chunks instead of reading it whole
+            loop {
+                match ctx.terminal.files().read(fd, CAT_CHUNK_SIZE) {
+                    Ok(data) if data.is_empty() => break,
+                    Ok(data) => ctx.write_bytes(&data),
+                    Err(e) => {
+                        ctx.print_fs_error("cat", name, e);
+                        status = 1;
+                        break;
+                    }
+                }
+            }
+            let _ = ctx.terminal.files().close(fd);
+        }
+        status
     }
 
     fn name(&self) -> &str {
@@ -140,7 +219,360 @@ impl Command for CatCommand {
     }
 
     fn description(&self) -> &str {
-        "Display the content of a file"
+        "Display the content of files or of the input"
+    }
+}
+
+/// The tail command for printing the end of a file
+pub struct TailCommand;
+
+// Number of bytes tail prints without -c
+const TAIL_DEFAULT_BYTES: u64 = 512;
+
+impl Command for TailCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        // Like tail -c on Unix, +N starts at the N-th byte instead of counting from the end
+        let (from_start, count, name) = match argv {
+            [_, name] => (false, Some(TAIL_DEFAULT_BYTES), *name),
+            [_, "-c", count, name] => match count.strip_prefix('+') {
+                Some(start) => (true, start.parse().ok(), *name),
+                None => (false, count.parse().ok(), *name),
+            },
+            _ => {
+                ctx.write_err("Usage: tail [-c [+]bytes] <file>\n");
+                return 2;
+            }
+        };
+        let count: u64 = match count {
+            Some(count) => count,
+            None => {
+                ctx.write_err(&format!("tail: invalid number of bytes '{}'\n", argv[2]));
+                return 1;
+            }
+        };
+
+        let path = ctx.terminal.resolve_path(name);
+        let fd = match ctx.terminal.files().open(&path, OpenFlags::READ) {
+            Ok(fd) => fd,
+            Err(e) => {
+                ctx.print_fs_error("tail", name, e);
+                return 1;
+            }
+        };
+
+        // Seek to where the output starts instead of reading the whole file
+        let files = ctx.terminal.files();
+        let position = if from_start {
+            files.seek(fd, SeekFrom::Start(count.saturating_sub(1)))
+        } else {
+            files
+                .stat(fd)
+                .and_then(|meta| files.seek(fd, SeekFrom::End(-(count.min(meta.size) as i64))))
+        };
+
+        let mut status = 0;
+        if let Err(e) = position {
+            ctx.print_fs_error("tail", name, e);
+            status = 1;
+        }
+        while status == 0 {
+            match ctx.terminal.files().read(fd, CAT_CHUNK_SIZE) {
+                Ok(data) if data.is_empty() => break,
+                Ok(data) => ctx.write_bytes(&data),
+                Err(e) => {
+                    ctx.print_fs_error("tail", name, e);
+                    status = 1;
+                }
+            }
+        }
+        let _ = ctx.terminal.files().close(fd);
+        status
+    }
+
+    fn name(&self) -> &str {
+        "tail"
+    }
+
+    fn description(&self) -> &str {
+        "Display the last bytes of a file, or from a byte on with -c +N"
+    }
+}
+
+/// The grep command for printing lines that contain a pattern
+pub struct GrepCommand;
+
+impl Command for GrepCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        let mut ignore_case = false;
+        let mut invert = false;
+        let mut operands = Vec::new();
+        for arg in &argv[1..] {
+            match *arg {
+                "-i" => ignore_case = true,
+                "-v" => invert = true,
+                operand => operands.push(operand),
+            }
+        }
+
+        let (pattern, names) = match operands.split_first() {
+            Some((pattern, names)) if !names.is_empty() || !ctx.stdin_is_terminal() => (*pattern, names),
+            _ => {
+                ctx.write_err("Usage: grep [-i] [-v] <pattern> [file...]\n");
+                return 2;
+            }
+        };
+        let pattern = if ignore_case { pattern.to_lowercase() } else { String::from(pattern) };
+
+        // Like on Unix, the status is 0 if a line was printed, 1 if none was and 2 on errors
+        if names.is_empty() {
+            return match ctx.read_to_end() {
+                Ok(data) if grep_lines(ctx, &data, &pattern, ignore_case, invert, None) => 0,
+                Ok(_) => 1,
+                Err(e) => {
+                    ctx.print_fs_error("grep", "stdin", e);
+                    2
+                }
+            };
+        }
+
+        let mut found = false;
+        let mut failed = false;
+        for name in names {
+            let path = ctx.terminal.resolve_path(name);
+            match ctx.terminal.read_file(&path) {
+                // With several files, each line is prefixed with its file name
+                Ok(data) => {
+                    let prefix = if names.len() > 1 { Some(*name) } else { None };
+                    found |= grep_lines(ctx, &data, &pattern, ignore_case, invert, prefix);
+                }
+                Err(e) => {
+                    ctx.print_fs_error("grep", name, e);
+                    failed = true;
+                }
+            }
+        }
+        if failed {
+            2
+        } else if found {
+            0
+        } else {
+            1
+        }
+    }
+
+    fn name(&self) -> &str {
+        "grep"
+    }
+
+    fn description(&self) -> &str {
+        "Print lines of files or of the input that contain a pattern"
+    }
+}
+
+/// Print the lines of `data` that contain `pattern` (or that do not, when inverted);
+/// returns whether any line was printed
+fn grep_lines(ctx: &mut Context, data: &[u8], pattern: &str, ignore_case: bool, invert: bool, prefix: Option<&str>) -> bool {
+    let mut printed = false;
+    let text = String::from_utf8_lossy(data);
+    for line in text.lines() {
+        let found = if ignore_case {
+            line.to_lowercase().contains(pattern)
+        } else {
+            line.contains(pattern)
+        };
+        if found != invert {
+            if let Some(prefix) = prefix {
+                ctx.write_str(prefix);
+                ctx.write_byte(b':');
+            }
+            ctx.write_str(line);
+            ctx.write_byte(b'\n');
+            printed = true;
+        }
+    }
+    printed
+}
+
+/// The touch command for creating empty files
+pub struct TouchCommand;
+
+impl Command for TouchCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        if argv.len() < 2 {
+            ctx.write_err("Usage: touch <file>...\n");
+            return 2;
+        }
+
+        let mut status = 0;
+        for name in &argv[1..] {
+            let path = ctx.terminal.resolve_path(name);
+            // Opening with CREATE makes the file if needed and leaves existing ones untouched
+            match ctx.terminal.files().open(&path, OpenFlags::READ | OpenFlags::CREATE) {
+                Ok(fd) => {
+                    let _ = ctx.terminal.files().close(fd);
+                }
+                Err(e) => {
+                    ctx.print_fs_error("touch", &format!("cannot touch '{}'", name), e);
+                    status = 1;
+                }
+            }
+        }
+        status
+    }
+
+    fn name(&self) -> &str {
+        "touch"
+    }
+
+    fn description(&self) -> &str {
+        "Create empty files"
+    }
+}
+
+/// The write command for replacing the content of a file
+pub struct WriteCommand;
+
+impl Command for WriteCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        if argv.len() < 2 {
+            ctx.write_err("Usage: write <file> [text...]\n");
+            return 2;
+        }
+        write_text(ctx, "write", argv[1], &argv[2..], OpenFlags::TRUNCATE)
+    }
+
+    fn name(&self) -> &str {
+        "write"
+    }
+
+    fn description(&self) -> &str {
+        "Write text to a file, replacing its content"
+    }
+}
+
+/// The append command for adding text to the end of a file
+pub struct AppendCommand;
+
+impl Command for AppendCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        if argv.len() < 2 {
+            ctx.write_err("Usage: append <file> [text...]\n");
+            return 2;
+        }
+        write_text(ctx, "append", argv[1], &argv[2..], OpenFlags::APPEND)
+    }
+
+    fn name(&self) -> &str {
+        "append"
+    }
+
+    fn description(&self) -> &str {
+        "Append text to the end of a file"
+    }
+}
+
+/// Write the words as one line to a file, creating it if needed
+fn write_text(ctx: &mut Context, command: &str, name: &str, words: &[&str], mode: OpenFlags) -> i32 {
+    let path = ctx.terminal.resolve_path(name);
+    let fd = match ctx.terminal.files().open(&path, OpenFlags::WRITE | OpenFlags::CREATE | mode) {
+        Ok(fd) => fd,
+        Err(e) => {
+            ctx.print_fs_error(command, name, e);
+            return 1;
+        }
+    };
+
+    let mut line = words.join(" ");
+    line.push('\n');
+    let status = match ctx.terminal.files().write(fd, line.as_bytes()) {
+        Ok(_) => 0,
+        Err(e) => {
+            ctx.print_fs_error(command, name, e);
+            1
+        }
+    };
+    let _ = ctx.terminal.files().close(fd);
+    status
+}
+
+/// The stat command for showing file metadata
+pub struct StatCommand;
+
+impl Command for StatCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        if argv.len() < 2 {
+            ctx.write_err("Usage: stat <path>...\n");
+            return 2;
+        }
+
+        use crate::fs::new_fs::FileSystem;
+        let fs = match ctx.terminal.vfs() {
+            Some(fs) => fs,
+            None => return 1,
+        };
+
+        let mut status = 0;
+        for name in &argv[1..] {
+            let path = ctx.terminal.resolve_path(name);
+            match fs.metadata(&path) {
+                Ok(meta) => {
+                    let inode = VfsInode::from_id(meta.inode_id);
+                    let kind = if meta.is_dir { "directory" } else { "regular file" };
+                    ctx.write_str(&format!("  File: {}\n", path));
+                    ctx.write_str(&format!("  Type: {}\n", kind));
+                    ctx.write_str(&format!(" Inode: {}  Device: {}\n", inode.ino, inode.mount_id));
+                    ctx.write_str(&format!("  Size: {} bytes  Blocks: {}\n", meta.size, meta.blocks));
+                }
+                Err(e) => {
+                    ctx.print_fs_error("stat", &format!("cannot stat '{}'", name), e);
+                    status = 1;
+                }
+            }
+        }
+        status
+    }
+
+    fn name(&self) -> &str {
+        "stat"
+    }
+
+    fn description(&self) -> &str {
+        "Show file or directory metadata"
+    }
+}
+
+/// The umount command for detaching a mounted filesystem
+pub struct UmountCommand;
+
+impl Command for UmountCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        let target = match argv {
+            [_, target] => *target,
+            _ => {
+                ctx.write_err("Usage: umount <mountpoint>\n");
+                return 2;
+            }
+        };
+
+        let fs = match ctx.terminal.vfs() {
+            Some(fs) => fs,
+            None => return 1,
+        };
+        match fs.unmount(&ctx.terminal.resolve_path(target)) {
+            Ok(()) => 0,
+            Err(e) => {
+                ctx.print_fs_error("umount", target, e);
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "umount"
+    }
+
+    fn description(&self) -> &str {
+        "Unmount the filesystem mounted on a directory"
     }
 }
 
@@ -148,23 +580,30 @@ impl Command for CatCommand {
 pub struct MkCommand;
 
 impl Command for MkCommand {
-    fn execute(&self, terminal: &mut Terminal, args: &[&str]) {
-        if args.is_empty() {
-            terminal.write_str("Usage: mk <directory_name>\n");
-            return;
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        if argv.len() < 2 {
+            ctx.write_err("Usage: mk <directory_name>\n");
+            return 2;
         }
 
-        let dir_name = args[0];
+        let dir_name = argv[1];
         
-        use crate::fs::new_fs::{SimpleFileSystem, MemoryBlockDevice};
-        let device = MemoryBlockDevice::new();
-        let mut fs = SimpleFileSystem::new(device);
-        fs.init();
+        use crate::fs::new_fs::FileSystem;
+        let fs = match ctx.terminal.vfs() {
+            Some(fs) => fs,
+            None => return 1,
+        };
 
-        // For now, we'll just print a message indicating the command is being implemented
-        terminal.write_str(&format!("Creating directory '{}'...\n", dir_name));
-        // TODO: Implement actual directory creation
-        terminal.write_str("Directory creation functionality is not yet fully implemented.\n");
+        match fs.create_directory(&ctx.terminal.resolve_path(dir_name), 0) {
+            Ok(_) => {
+                ctx.write_str(&format!("Directory '{}' created successfully\n", dir_name));
+                0
+            }
+            Err(e) => {
+                ctx.print_fs_error("mk", &format!("cannot create directory '{}'", dir_name), e);
+                1
+            }
+        }
     }
 
     fn name(&self) -> &str {
@@ -180,16 +619,60 @@ impl Command for MkCommand {
 pub struct RmCommand;
 
 impl Command for RmCommand {
-    fn execute(&self, terminal: &mut Terminal, args: &[&str]) {
-        if args.is_empty() {
-            terminal.write_str("Usage: rm [-r] [-f] <name>\n");
-            return;
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        let mut recursive = false;
+        let mut force = false;
+        let mut names = Vec::new();
+
+        // Parse options and arguments
+        for arg in &argv[1..] {
+            match *arg {
+                "-r" => recursive = true,
+                "-f" => force = true,
+                _ => names.push(*arg),
+            }
+        }
+
+        if names.is_empty() {
+            ctx.write_err("Usage: rm [-r] [-f] <name>...\n");
+            return 2;
         }
 
-        // For now, we'll just print a message indicating the command is being implemented
-        terminal.write_str("Removing file or directory...\n");
-        // TODO: Implement actual file/directory removal
-        terminal.write_str("File/directory removal functionality is not yet fully implemented.\n");
+        use crate::fs::new_fs::{FileSystem, FsError};
+        let mut status = 0;
+        for name in names {
+            if !force {
+                // 提示写到终端，回答从标准输入读取；没有回答时不删除
+                ctx.write_err(&format!("Are you sure you want to delete '{}'{}? (y/N): ",
+                                    name, if recursive { " and all its contents" } else { "" }));
+                let response = ctx.read_line();
+                if !matches!(response.as_deref(), Some("y" | "Y")) {
+                    ctx.write_err("Deletion cancelled\n");
+                    status = 1;
+                    continue;
+                }
+            }
+
+            let fs = match ctx.terminal.vfs() {
+                Some(fs) => fs,
+                None => return 1,
+            };
+
+            match fs.delete_item(&ctx.terminal.resolve_path(name), recursive) {
+                Ok(_) => ctx.write_str(&format!("'{}' deleted successfully\n", name)),
+                // Like rm -f on Unix, a missing file is not an error when forcing
+                Err(FsError::NotFound) if force => {}
+                Err(FsError::DirectoryNotEmpty) if !recursive => {
+                    ctx.write_err(&format!("rm: cannot remove '{}': Directory not empty (use -r)\n", name));
+                    status = 1;
+                }
+                Err(e) => {
+                    ctx.print_fs_error("rm", &format!("cannot remove '{}'", name), e);
+                    status = 1;
+                }
+            }
+        }
+        status
     }
 
     fn name(&self) -> &str {
@@ -205,18 +688,17 @@ impl Command for RmCommand {
 pub struct CdCommand;
 
 impl Command for CdCommand {
-    fn execute(&self, terminal: &mut Terminal, args: &[&str]) {
-        if args.is_empty() {
-            terminal.write_str("Usage: cd <directory_path>\n");
-            return;
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        // Without an argument, go to $HOME
+        let home = String::from(ctx.terminal.env.get("HOME").unwrap_or("/"));
+        let dir_path = argv.get(1).copied().unwrap_or(&home);
+        match ctx.terminal.change_directory(dir_path) {
+            Ok(()) => 0,
+            Err(e) => {
+                ctx.print_fs_error("cd", dir_path, e);
+                1
+            }
         }
-
-        let dir_path = args[0];
-        
-        // For now, we'll just print a message indicating the command is being implemented
-        terminal.write_str(&format!("Changing directory to '{}'...\n", dir_path));
-        // TODO: Implement actual directory change
-        terminal.write_str("Directory change functionality is not yet fully implemented.\n");
     }
 
     fn name(&self) -> &str {
@@ -228,23 +710,55 @@ impl Command for CdCommand {
     }
 }
 
+/// The pwd command for printing the current directory
+pub struct PwdCommand;
+
+impl Command for PwdCommand {
+    fn execute(&self, ctx: &mut Context, _argv: &[&str]) -> i32 {
+        let cwd = String::from(ctx.terminal.cwd());
+        ctx.write_str(&cwd);
+        ctx.write_byte(b'\n');
+        0
+    }
+
+    fn name(&self) -> &str {
+        "pwd"
+    }
+
+    fn description(&self) -> &str {
+        "Print the current directory"
+    }
+}
+
 /// The mv command for moving files or directories
 pub struct MvCommand;
 
 impl Command for MvCommand {
-    fn execute(&self, terminal: &mut Terminal, args: &[&str]) {
-        if args.len() < 2 {
-            terminal.write_str("Usage: mv <source> <destination>\n");
-            return;
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        if argv.len() < 3 {
+            ctx.write_err("Usage: mv <source> <destination>\n");
+            return 2;
         }
 
-        let source = args[0];
-        let destination = args[1];
-        
-        // For now, we'll just print a message indicating the command is being implemented
-        terminal.write_str(&format!("Moving '{}' to '{}'...\n", source, destination));
-        // TODO: Implement actual file/directory move
-        terminal.write_str("File/directory move functionality is not yet fully implemented.\n");
+        let source = argv[1];
+        let destination = argv[2];
+
+        use crate::fs::new_fs::FileSystem;
+        let fs = match ctx.terminal.vfs() {
+            Some(fs) => fs,
+            None => return 1,
+        };
+
+        match fs.move_item(&ctx.terminal.resolve_path(source), &ctx.terminal.resolve_path(destination)) {
+            Ok(_) => {
+                ctx.write_str(&format!("Moved '{}' to '{}'\n", source, destination));
+                0
+            }
+            Err(e) => {
+                ctx.print_fs_error("mv", &format!("cannot move '{}' to '{}'", source, destination), e);
+                1
+            }
+        }
     }
 
     fn name(&self) -> &str {
@@ -260,37 +774,50 @@ impl Command for MvCommand {
 pub struct CpCommand;
 
 impl Command for CpCommand {
-    fn execute(&self, terminal: &mut Terminal, args: &[&str]) {
-        if args.len() < 2 {
-            terminal.write_str("Usage: cp [-r] <source> <destination>\n");
-            return;
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        if argv.len() < 3 {
+            ctx.write_err("Usage: cp [-r] <source> <destination>\n");
+            return 2;
         }
 
         // Parse options
         let mut recursive = false;
-        let mut source_index = 0;
+        let mut source_index = 1;
         
-        if args[0] == "-r" {
+        if argv[1] == "-r" {
             recursive = true;
-            source_index = 1;
+            source_index = 2;
         }
         
-        if args.len() < source_index + 2 {
-            terminal.write_str("Usage: cp [-r] <source> <destination>\n");
-            return;
+        if argv.len() < source_index + 2 {
+            ctx.write_err("Usage: cp [-r] <source> <destination>\n");
+            return 2;
         }
 
-        let source = args[source_index];
-        let destination = args[source_index + 1];
-        
-        // For now, we'll just print a message indicating the command is being implemented
-        if recursive {
-            terminal.write_str(&format!("Recursively copying '{}' to '{}'...\n", source, destination));
-        } else {
-            terminal.write_str(&format!("Copying '{}' to '{}'...\n", source, destination));
+        let source = argv[source_index];
+        let destination = argv[source_index + 1];
+
+        use crate::fs::new_fs::{FileSystem, FsError};
+        let fs = match ctx.terminal.vfs() {
+            Some(fs) => fs,
+            None => return 1,
+        };
+
+        match fs.copy_item(&ctx.terminal.resolve_path(source), &ctx.terminal.resolve_path(destination), recursive) {
+            Ok(_) => {
+                ctx.write_str(&format!("Copied '{}' to '{}'{}\n", source, destination,
+                                       if recursive { " recursively" } else { "" }));
+                0
+            }
+            Err(FsError::IsADirectory) if !recursive => {
+                ctx.write_err(&format!("cp: -r not specified; omitting directory '{}'\n", source));
+                1
+            }
+            Err(e) => {
+                ctx.print_fs_error("cp", &format!("cannot copy '{}' to '{}'", source, destination), e);
+                1
+            }
         }
-        // TODO: Implement actual file/directory copy
-        terminal.write_str("File/directory copy functionality is not yet fully implemented.\n");
     }
 
     fn name(&self) -> &str {
@@ -302,25 +829,845 @@ impl Command for CpCommand {
     }
 }
 
-/// Get all available commands
-pub fn get_commands() -> [&'static dyn Command; 10] {
-    [
-        &HelpCommand,
-        &ClearCommand,
-        &EchoCommand,
-        &LsCommand,
-        &CatCommand,
-        &MkCommand,
-        &RmCommand,
-        &CdCommand,
-        &MvCommand,
-        &CpCommand,
-    ]
+/// The meminfo command
+pub struct MeminfoCommand;
+
+impl Command for MeminfoCommand {
+    fn execute(&self, ctx: &mut Context, _argv: &[&str]) -> i32 {
+        let monitor = SystemMonitor::new(ctx.terminal.allocator);
+        monitor.display_memory_info(ctx);
+        0
+    }
+
+    fn name(&self) -> &str {
+        "meminfo"
+    }
+
+    fn description(&self) -> &str {
+        "Display memory usage"
+    }
+}
+
+/// The memstats command
+pub struct MemstatsCommand;
+
+impl Command for MemstatsCommand {
+    fn execute(&self, ctx: &mut Context, _argv: &[&str]) -> i32 {
+        let allocator = ctx.terminal.allocator;
+        let monitor = SystemMonitor::new(allocator);
+        monitor.display_memory_info(ctx);
+        
+        // 添加详细统计信息
+        ctx.write_str("\n=== 详细统计信息 ===\n");
+        ctx.write_str("分配次数:      ");
+        let alloc_count = allocator.get_allocation_count();
+        ctx.write_str(&format!("{}\n", alloc_count));
+        
+        ctx.write_str("释放次数:      ");
+        let dealloc_count = allocator.get_deallocation_count();
+        ctx.write_str(&format!("{}\n", dealloc_count));
+        
+        if let Some(avg_size) = allocator.get_total_allocated().checked_div(alloc_count) {
+            ctx.write_str("平均分配大小:  ");
+            ctx.write_str(&format!("{} 字节\n", avg_size));
+        }
+
+        ctx.write_str("\n");
+        monitor.display_slab_stats(ctx);
+        0
+    }
+
+    fn name(&self) -> &str {
+        "memstats"
+    }
+
+    fn description(&self) -> &str {
+        "Display detailed allocator statistics"
+    }
 }
 
-/// Find a command by name
-pub fn find_command(name: &str) -> Option<&'static dyn Command> {
-    get_commands().iter()
-        .find(|cmd| cmd.name() == name)
-        .copied()
-}
\ No newline at end of file
+/// The sysinfo command
+pub struct SysinfoCommand;
+
+impl Command for SysinfoCommand {
+    fn execute(&self, ctx: &mut Context, _argv: &[&str]) -> i32 {
+        let monitor = SystemMonitor::new(ctx.terminal.allocator);
+        monitor.display_system_info(ctx);
+        
+        // 添加一些额外的系统信息
+        ctx.write_str("\n=== 内存信息 ===\n");
+        ctx.write_str(&format!(
+            "堆地址范围:    0x{:X} - 0x{:X}\n",
+            crate::allocator::HEAP_START,
+            crate::allocator::HEAP_START as u64 + ctx.terminal.allocator.get_memory_stats().total_heap_size
+        ));
+        
+        ctx.write_str("分配器类型:    链表分配器\n");
+        ctx.write_str("双缓冲模式:    已启用\n");
+        ctx.write_str("终端模式:      交互式\n");
+        0
+    }
+
+    fn name(&self) -> &str {
+        "sysinfo"
+    }
+
+    fn description(&self) -> &str {
+        "Display system information"
+    }
+}
+
+/// The syshealth command
+pub struct SyshealthCommand;
+
+impl Command for SyshealthCommand {
+    fn execute(&self, ctx: &mut Context, _argv: &[&str]) -> i32 {
+        let monitor = SystemMonitor::new(ctx.terminal.allocator);
+        monitor.display_health_check(ctx);
+        
+        // 添加简单的系统自检
+        ctx.write_str("\n=== 系统自检 ===\n");
+        
+        // 检查内存分配器状态
+        if ctx.terminal.allocator.get_allocation_count() > 0 {
+            ctx.write_str("✅ 内存分配器:  正常\n");
+        } else {
+            ctx.write_str("⚠️  内存分配器:  未使用\n");
+        }
+        
+        // 检查VGA缓冲
+        ctx.write_str("✅ VGA缓冲:     正常\n");
+        
+        // 检查终端状态
+        ctx.write_str("✅ 终端系统:    正常\n");
+        
+        // 检查文件系统
+        ctx.write_str("✅ 文件系统:    可用\n");
+        
+        ctx.write_str("\n系统状态:      全部检查通过 ✅\n");
+        0
+    }
+
+    fn name(&self) -> &str {
+        "syshealth"
+    }
+
+    fn description(&self) -> &str {
+        "Run a system health check"
+    }
+}
+
+/// The free command
+pub struct FreeCommand;
+
+impl Command for FreeCommand {
+    fn execute(&self, ctx: &mut Context, _argv: &[&str]) -> i32 {
+        let monitor = SystemMonitor::new(ctx.terminal.allocator);
+        monitor.display_free(ctx);
+        0
+    }
+
+    fn name(&self) -> &str {
+        "free"
+    }
+
+    fn description(&self) -> &str {
+        "Display total, used and free physical memory and heap"
+    }
+}
+
+/// The frames command
+pub struct FramesCommand;
+
+impl Command for FramesCommand {
+    fn execute(&self, ctx: &mut Context, _argv: &[&str]) -> i32 {
+        let monitor = SystemMonitor::new(ctx.terminal.allocator);
+        monitor.display_frames(ctx);
+        0
+    }
+
+    fn name(&self) -> &str {
+        "frames"
+    }
+
+    fn description(&self) -> &str {
+        "Display memory regions and physical frame usage"
+    }
+}
+
+/// The heap command for showing the heap size and setting how far it may grow
+pub struct HeapCommand;
+
+impl Command for HeapCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        let allocator = ctx.terminal.allocator;
+        match &argv[1..] {
+            [] => {
+                let stats = allocator.get_memory_stats();
+                let line = format!(
+                    "Heap: {} of {} KiB, grown {} times ({} failed)\n",
+                    stats.total_heap_size / 1024,
+                    stats.heap_limit / 1024,
+                    stats.growth_count,
+                    stats.growth_failures,
+                );
+                ctx.write_str(&line);
+                0
+            }
+            ["-l", limit] => match limit.parse::<usize>().ok().and_then(|kib| kib.checked_mul(1024)) {
+                Some(bytes) if allocator.set_heap_limit(bytes) => 0,
+                Some(_) => {
+                    let size = allocator.get_memory_stats().total_heap_size / 1024;
+                    ctx.write_err(&format!("heap: limit is below the current heap size of {} KiB\n", size));
+                    1
+                }
+                None => {
+                    ctx.write_err(&format!("heap: invalid limit '{}'\n", limit));
+                    1
+                }
+            },
+            _ => {
+                ctx.write_err("Usage: heap [-l KiB]\n");
+                2
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "heap"
+    }
+
+    fn description(&self) -> &str {
+        "Show the heap size or set the limit (-l) it may grow to"
+    }
+}
+
+/// The keymap command for selecting the keyboard layout
+pub struct KeymapCommand;
+
+impl Command for KeymapCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        use crate::keyboard::Layout;
+
+        let name = match argv.get(1) {
+            Some(name) => *name,
+            None => {
+                let current = ctx.terminal.keyboard.layout().name();
+                ctx.write_str(&format!("Current layout: {}\n", current));
+                let modifiers = ctx.terminal.keyboard.modifiers();
+                let state = |on: bool| if on { "on" } else { "off" };
+                ctx.write_str(&format!(
+                    "Caps Lock: {}, Num Lock: {}, Scroll Lock: {}\n",
+                    state(modifiers.caps_lock),
+                    state(modifiers.num_lock),
+                    state(modifiers.scroll_lock)
+                ));
+                ctx.write_str("Available layouts:");
+                for layout in Layout::ALL {
+                    ctx.write_str(&format!(" {}", layout.name()));
+                }
+                ctx.write_byte(b'\n');
+                return 0;
+            }
+        };
+
+        match Layout::from_name(name) {
+            Some(layout) => {
+                ctx.terminal.keyboard.set_layout(layout);
+                ctx.write_str(&format!("Keyboard layout set to {}\n", layout.name()));
+                0
+            }
+            None => {
+                ctx.write_err(&format!("keymap: unknown layout '{}'\n", name));
+                1
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "keymap"
+    }
+
+    fn description(&self) -> &str {
+        "Show or select the keyboard layout (us, uk, de, fr)"
+    }
+}
+
+/// The history command for listing or configuring the command history
+pub struct HistoryCommand;
+
+impl Command for HistoryCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        match &argv[1..] {
+            [] => {
+                let lines: Vec<String> = ctx.terminal.history
+                    .iter()
+                    .enumerate()
+                    .map(|(i, entry)| format!("{:>4}  {}\n", i + 1, entry))
+                    .collect();
+                for line in lines {
+                    ctx.write_str(&line);
+                }
+                0
+            }
+            ["-c"] => {
+                ctx.terminal.history.clear();
+                0
+            }
+            ["-s"] => {
+                let depth = ctx.terminal.history.depth();
+                ctx.write_str(&format!("History depth: {}\n", depth));
+                0
+            }
+            ["-s", depth] => match depth.parse::<usize>() {
+                Ok(depth) => {
+                    ctx.terminal.history.set_depth(depth);
+                    0
+                }
+                Err(_) => {
+                    ctx.write_err(&format!("history: invalid depth '{}'\n", depth));
+                    1
+                }
+            },
+            _ => {
+                ctx.write_err("Usage: history [-c] [-s [depth]]\n");
+                2
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "history"
+    }
+
+    fn description(&self) -> &str {
+        "List, clear (-c) or resize (-s) the command history"
+    }
+}
+
+/// The scrollback command for configuring the history of rows scrolled off the screen
+pub struct ScrollbackCommand;
+
+impl Command for ScrollbackCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        match &argv[1..] {
+            [] => {
+                let scrollback = &ctx.terminal.scrollback;
+                let line = format!("Scrollback: {} of {} lines\n", scrollback.len(), scrollback.capacity());
+                ctx.write_str(&line);
+                0
+            }
+            ["-c"] => {
+                ctx.terminal.scrollback.clear();
+                0
+            }
+            ["-s", lines] => match lines.parse::<usize>() {
+                Ok(lines) => {
+                    ctx.terminal.scrollback.set_capacity(lines);
+                    0
+                }
+                Err(_) => {
+                    ctx.write_err(&format!("scrollback: invalid size '{}'\n", lines));
+                    1
+                }
+            },
+            _ => {
+                ctx.write_err("Usage: scrollback [-c] [-s lines]\n");
+                2
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "scrollback"
+    }
+
+    fn description(&self) -> &str {
+        "Show, clear (-c) or resize (-s) the scrollback viewed with Shift+PageUp/PageDown"
+    }
+}
+
+/// The export command for adding variables to the environment
+pub struct ExportCommand;
+
+impl Command for ExportCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        if argv.len() < 2 {
+            let lines: Vec<String> = ctx.terminal.env
+                .exported()
+                .map(|(name, value)| format!("export {}=\"{}\"\n", name, value))
+                .collect();
+            for line in lines {
+                ctx.write_str(&line);
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        for arg in &argv[1..] {
+            match env::parse_assignment(arg) {
+                Some((name, value)) => ctx.terminal.env.export(name, value),
+                None if parser::is_valid_name(arg) => ctx.terminal.env.mark_exported(arg),
+                None => {
+                    ctx.write_err(&format!("export: '{}': not a valid identifier\n", arg));
+                    status = 1;
+                }
+            }
+        }
+        status
+    }
+
+    fn name(&self) -> &str {
+        "export"
+    }
+
+    fn description(&self) -> &str {
+        "Set environment variables (NAME=value) or list them"
+    }
+}
+
+/// The unset command for removing variables
+pub struct UnsetCommand;
+
+impl Command for UnsetCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        if argv.len() < 2 {
+            ctx.write_err("Usage: unset <name>...\n");
+            return 2;
+        }
+
+        let mut status = 0;
+        for name in &argv[1..] {
+            if !parser::is_valid_name(name) {
+                ctx.write_err(&format!("unset: '{}': not a valid identifier\n", name));
+                status = 1;
+                continue;
+            }
+            // Like on Unix, removing a variable that does not exist is not an error
+            ctx.terminal.env.unset(name);
+        }
+        status
+    }
+
+    fn name(&self) -> &str {
+        "unset"
+    }
+
+    fn description(&self) -> &str {
+        "Remove shell variables"
+    }
+}
+
+/// The env command for listing the environment
+pub struct EnvCommand;
+
+impl Command for EnvCommand {
+    fn execute(&self, ctx: &mut Context, _argv: &[&str]) -> i32 {
+        let lines: Vec<String> = ctx.terminal.env
+            .exported()
+            .map(|(name, value)| format!("{}={}\n", name, value))
+            .collect();
+        for line in lines {
+            ctx.write_str(&line);
+        }
+        0
+    }
+
+    fn name(&self) -> &str {
+        "env"
+    }
+
+    fn description(&self) -> &str {
+        "List the environment variables"
+    }
+}
+
+/// The set command for listing or assigning shell variables
+pub struct SetCommand;
+
+impl Command for SetCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        if argv.len() < 2 {
+            let lines: Vec<String> = ctx.terminal.env
+                .iter()
+                .map(|(name, value)| format!("{}={}\n", name, value))
+                .collect();
+            for line in lines {
+                ctx.write_str(&line);
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        for arg in &argv[1..] {
+            match env::parse_assignment(arg) {
+                Some((name, value)) => ctx.terminal.env.set(name, value),
+                None => {
+                    ctx.write_err(&format!("set: '{}': expected NAME=value\n", arg));
+                    status = 1;
+                }
+            }
+        }
+        status
+    }
+
+    fn name(&self) -> &str {
+        "set"
+    }
+
+    fn description(&self) -> &str {
+        "Set shell variables (NAME=value) or list all of them"
+    }
+}
+
+/// The true command, which always succeeds
+pub struct TrueCommand;
+
+impl Command for TrueCommand {
+    fn execute(&self, _ctx: &mut Context, _argv: &[&str]) -> i32 {
+        0
+    }
+
+    fn name(&self) -> &str {
+        "true"
+    }
+
+    fn description(&self) -> &str {
+        "Do nothing, successfully"
+    }
+}
+
+/// The false command, which always fails
+pub struct FalseCommand;
+
+impl Command for FalseCommand {
+    fn execute(&self, _ctx: &mut Context, _argv: &[&str]) -> i32 {
+        1
+    }
+
+    fn name(&self) -> &str {
+        "false"
+    }
+
+    fn description(&self) -> &str {
+        "Do nothing, unsuccessfully"
+    }
+}
+
+/// The test command for checking files, strings and numbers
+pub struct TestCommand;
+
+impl Command for TestCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        run_test(ctx, "test", &argv[1..])
+    }
+
+    fn name(&self) -> &str {
+        "test"
+    }
+
+    fn description(&self) -> &str {
+        "Check a condition: -e/-f/-d path, -n/-z string, = != -eq -ne -lt -le -gt -ge"
+    }
+}
+
+/// The [ command, the same as test but closed with ]
+pub struct BracketCommand;
+
+impl Command for BracketCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        match argv[1..].split_last() {
+            Some((&"]", args)) => run_test(ctx, "[", args),
+            _ => {
+                ctx.write_err("[: missing ']'\n");
+                2
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "["
+    }
+
+    fn description(&self) -> &str {
+        "Check a condition, like test"
+    }
+}
+
+/// Evaluate a test expression: the status is 0 if it is true, 1 if false and 2 if invalid
+fn run_test(ctx: &mut Context, command: &str, args: &[&str]) -> i32 {
+    match evaluate_test(ctx, args) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(message) => {
+            ctx.write_err(&format!("{}: {}\n", command, message));
+            2
+        }
+    }
+}
+
+// 二元比较运算符
+const TEST_BINARY_OPERATORS: [&str; 9] = ["=", "==", "!=", "-eq", "-ne", "-lt", "-le", "-gt", "-ge"];
+
+fn evaluate_test(ctx: &mut Context, args: &[&str]) -> Result<bool, String> {
+    match args {
+        [] => Ok(false),
+        [left, operator, right] if TEST_BINARY_OPERATORS.contains(operator) => binary_test(left, operator, right),
+        ["!", rest @ ..] => evaluate_test(ctx, rest).map(|result| !result),
+        [string] => Ok(!string.is_empty()),
+        [operator, operand] => unary_test(ctx, operator, operand),
+        _ => Err(String::from("too many arguments")),
+    }
+}
+
+fn unary_test(ctx: &mut Context, operator: &str, operand: &str) -> Result<bool, String> {
+    use crate::fs::new_fs::FileSystem;
+
+    match operator {
+        "-n" => Ok(!operand.is_empty()),
+        "-z" => Ok(operand.is_empty()),
+        "-e" | "-f" | "-d" => {
+            let path = ctx.terminal.resolve_path(operand);
+            let metadata = crate::fs::vfs().and_then(|vfs| vfs.metadata(&path));
+            Ok(match metadata {
+                Ok(meta) if operator == "-f" => !meta.is_dir,
+                Ok(meta) if operator == "-d" => meta.is_dir,
+                Ok(_) => true,
+                Err(_) => false,
+            })
+        }
+        _ => Err(format!("{}: unary operator expected", operator)),
+    }
+}
+
+fn binary_test(left: &str, operator: &str, right: &str) -> Result<bool, String> {
+    fn integer(value: &str) -> Result<i64, String> {
+        value.parse().map_err(|_| format!("{}: integer expression expected", value))
+    }
+
+    Ok(match operator {
+        "=" | "==" => left == right,
+        "!=" => left != right,
+        "-eq" => integer(left)? == integer(right)?,
+        "-ne" => integer(left)? != integer(right)?,
+        "-lt" => integer(left)? < integer(right)?,
+        "-le" => integer(left)? <= integer(right)?,
+        "-gt" => integer(left)? > integer(right)?,
+        _ => integer(left)? >= integer(right)?,
+    })
+}
+
+/// The sh command for running scripts
+pub struct ShCommand;
+
+impl Command for ShCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        match argv {
+            [_, "-c", source] => interpreter::run_source(ctx, source),
+            [_, path, args @ ..] if *path != "-c" => interpreter::run_script(ctx, path, args),
+            _ => {
+                ctx.write_err("Usage: sh <file> [args...] | sh -c <command>\n");
+                2
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "sh"
+    }
+
+    fn description(&self) -> &str {
+        "Run a script file, or a command line with -c"
+    }
+}
+
+/// Parse the optional status argument of exit and return, defaulting to `$?`
+fn status_argument(ctx: &mut Context, argv: &[&str]) -> Option<i32> {
+    match argv.get(1) {
+        None => Some(ctx.terminal.interpreter.last_status()),
+        Some(arg) => match arg.parse() {
+            Ok(status) => Some(status),
+            Err(_) => {
+                ctx.write_err(&format!("{}: {}: numeric argument required\n", argv[0], arg));
+                None
+            }
+        },
+    }
+}
+
+/// The exit command for leaving a script
+pub struct ExitCommand;
+
+impl Command for ExitCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        if !ctx.terminal.interpreter.in_script() {
+            ctx.write_err("exit: not running a script\n");
+            return 1;
+        }
+        let status = status_argument(ctx, argv).unwrap_or(2);
+        ctx.terminal.interpreter.set_flow(Flow::Exit);
+        status
+    }
+
+    fn name(&self) -> &str {
+        "exit"
+    }
+
+    fn description(&self) -> &str {
+        "Leave the running script with a status"
+    }
+}
+
+/// The return command for leaving a function or script
+pub struct ReturnCommand;
+
+impl Command for ReturnCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        if !ctx.terminal.interpreter.in_call() {
+            ctx.write_err("return: can only be used in a function or script\n");
+            return 1;
+        }
+        let status = status_argument(ctx, argv).unwrap_or(2);
+        ctx.terminal.interpreter.set_flow(Flow::Return);
+        status
+    }
+
+    fn name(&self) -> &str {
+        "return"
+    }
+
+    fn description(&self) -> &str {
+        "Leave the running function or script with a status"
+    }
+}
+
+/// Parse the optional loop count of break and continue, limited to the enclosing loops
+fn loop_count(ctx: &mut Context, argv: &[&str]) -> Option<usize> {
+    let depth = ctx.terminal.interpreter.loop_depth();
+    if depth == 0 {
+        ctx.write_err(&format!("{}: only meaningful in a loop\n", argv[0]));
+        return None;
+    }
+    match argv.get(1).map(|count| count.parse::<usize>()) {
+        None => Some(1),
+        Some(Ok(count)) if count > 0 => Some(count.min(depth)),
+        _ => {
+            ctx.write_err(&format!("{}: {}: loop count out of range\n", argv[0], argv[1]));
+            None
+        }
+    }
+}
+
+/// The break command for leaving loops
+pub struct BreakCommand;
+
+impl Command for BreakCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        match loop_count(ctx, argv) {
+            Some(count) => {
+                ctx.terminal.interpreter.set_flow(Flow::Break(count));
+                0
+            }
+            None => 1,
+        }
+    }
+
+    fn name(&self) -> &str {
+        "break"
+    }
+
+    fn description(&self) -> &str {
+        "Leave the innermost loop, or n loops"
+    }
+}
+
+/// The continue command for starting the next loop iteration
+pub struct ContinueCommand;
+
+impl Command for ContinueCommand {
+    fn execute(&self, ctx: &mut Context, argv: &[&str]) -> i32 {
+        match loop_count(ctx, argv) {
+            Some(count) => {
+                ctx.terminal.interpreter.set_flow(Flow::Continue(count));
+                0
+            }
+            None => 1,
+        }
+    }
+
+    fn name(&self) -> &str {
+        "continue"
+    }
+
+    fn description(&self) -> &str {
+        "Go to the next iteration of the innermost loop, or of the n-th loop"
+    }
+}
+
+/// Commands built into the shell
+const BUILTIN_COMMANDS: [&'static dyn Command; 35] = [
+    &HelpCommand,
+    &ClearCommand,
+    &EchoCommand,
+    &LsCommand,
+    &CatCommand,
+    &TailCommand,
+    &GrepCommand,
+    &TouchCommand,
+    &WriteCommand,
+    &AppendCommand,
+    &StatCommand,
+    &UmountCommand,
+    &MkCommand,
+    &RmCommand,
+    &CdCommand,
+    &PwdCommand,
+    &MvCommand,
+    &CpCommand,
+    &HeapCommand,
+    &KeymapCommand,
+    &HistoryCommand,
+    &ScrollbackCommand,
+    &ExportCommand,
+    &UnsetCommand,
+    &EnvCommand,
+    &SetCommand,
+    &TrueCommand,
+    &FalseCommand,
+    &TestCommand,
+    &BracketCommand,
+    &ShCommand,
+    &ExitCommand,
+    &ReturnCommand,
+    &BreakCommand,
+    &ContinueCommand,
+];
+
+// Commands registered at runtime, looked up after the built-ins
+static REGISTERED_COMMANDS: RwLock<Vec<&'static dyn Command>> = RwLock::new(Vec::new());
+
+/// Register a new command; fails if a command with the same name already exists
+pub fn register_command(command: &'static dyn Command) -> Result<(), &'static str> {
+    let mut registered = REGISTERED_COMMANDS.write();
+    let exists = BUILTIN_COMMANDS.iter().chain(registered.iter()).any(|cmd| cmd.name() == command.name());
+    if exists {
+        return Err("Command already registered");
+    }
+    registered.push(command);
+    Ok(())
+}
+
+/// Get all available commands, built-ins first
+pub fn get_commands() -> Vec<&'static dyn Command> {
+    let mut commands = BUILTIN_COMMANDS.to_vec();
+    commands.extend(REGISTERED_COMMANDS.read().iter().copied());
+    commands
+}
+
+/// Find a command by name
+pub fn find_command(name: &str) -> Option<&'static dyn Command> {
+    BUILTIN_COMMANDS.iter()
+        .chain(REGISTERED_COMMANDS.read().iter())
+        .find(|cmd| cmd.name() == name)
+        .copied()
+}
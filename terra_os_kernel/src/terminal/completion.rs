@@ -0,0 +1,106 @@
+//! Tab completion of command names and filesystem paths
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use super::commands;
+
+/// Completion candidates for the word before the cursor
+pub struct Completion {
+    /// The part of the word being completed (after the last '/' for paths)
+    pub prefix: String,
+    /// Matching names; directories end with '/'
+    pub candidates: Vec<String>,
+}
+
+impl Completion {
+    /// The longest text shared by all candidates
+    pub fn common_prefix(&self) -> String {
+        let mut candidates = self.candidates.iter();
+        let mut common = match candidates.next() {
+            Some(first) => first.clone(),
+            None => return String::new(),
+        };
+        for candidate in candidates {
+            while !candidate.starts_with(common.as_str()) {
+                common.pop();
+            }
+        }
+        common
+    }
+}
+
+/// Keywords that are followed by a command
+const COMMAND_KEYWORDS: [&str; 8] = ["if", "elif", "then", "else", "while", "until", "do", "{"];
+
+/// Complete the word before `cursor`: the first word of each command is a command name, later words are paths
+pub fn complete(cwd: &str, line: &[char], cursor: usize) -> Completion {
+    // 操作符不需要空格分隔，"cd /;ec"中要补全的是"ec"
+    let word_start = line[..cursor]
+        .iter()
+        .rposition(|&c| matches!(c, ' ' | ';' | '|' | '&'))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let word: String = line[word_start..cursor].iter().collect();
+    // 行首、';'、'|'、'&&'、'||'和控制关键字之后的单词是命令名
+    let before: String = line[..word_start].iter().collect();
+    let before = before.trim_end();
+    let last_word = before.rsplit(' ').next().unwrap_or("");
+    let is_command = before.is_empty()
+        || before.ends_with([';', '|', '&'])
+        || COMMAND_KEYWORDS.contains(&last_word);
+
+    if is_command {
+        complete_command(word)
+    } else {
+        complete_path(cwd, &word)
+    }
+}
+
+fn complete_command(prefix: String) -> Completion {
+    let mut candidates: Vec<String> = commands::get_commands()
+        .iter()
+        .map(|cmd| cmd.name())
+        .filter(|name| name.starts_with(prefix.as_str()))
+        .map(|name| name.to_string())
+        .collect();
+    candidates.sort();
+    Completion { prefix, candidates }
+}
+
+fn complete_path(cwd: &str, word: &str) -> Completion {
+    use crate::fs::new_fs::FileSystem;
+
+    // 在最后一个'/'处分成目录部分和要补全的名称部分
+    let (dir, prefix) = match word.rfind('/') {
+        Some(index) => (&word[..index + 1], &word[index + 1..]),
+        None => ("", word),
+    };
+    let dir_path = crate::fs::resolve_path(cwd, if dir.is_empty() { "." } else { dir });
+
+    let entries = crate::fs::vfs()
+        .and_then(|vfs| {
+            let inode_id = vfs.metadata(&dir_path)?.inode_id;
+            vfs.list_directory(inode_id)
+        })
+        .unwrap_or_default();
+
+    let mut candidates: Vec<String> = entries
+        .into_iter()
+        .filter(|(name, _, _, _)| name.starts_with(prefix))
+        // 只有明确输入了'.'时才补全.和..
+        .filter(|(name, _, _, _)| prefix.starts_with('.') || (name != "." && name != ".."))
+        .map(|(mut name, _, is_dir, _)| {
+            if is_dir {
+                name.push('/');
+            }
+            name
+        })
+        .collect();
+    candidates.sort();
+
+    Completion {
+        prefix: prefix.to_string(),
+        candidates,
+    }
+}
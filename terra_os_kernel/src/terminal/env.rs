@@ -0,0 +1,122 @@
+//! Shell variables of a terminal session
+//!
+//! Every variable can be expanded with `$NAME`; exported ones form the environment
+//! listed by `env`.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+use super::parser::is_valid_name;
+
+struct Variable {
+    value: String,
+    exported: bool,
+}
+
+/// The variables of a terminal session, kept sorted by name
+pub struct Environment {
+    variables: BTreeMap<String, Variable>,
+}
+
+impl Environment {
+    /// Create the environment of a new session with the well-known variables set
+    pub fn new() -> Self {
+        let mut env = Environment { variables: BTreeMap::new() };
+        env.export("HOME", "/");
+        env.export("PWD", "/");
+        env.export("PATH", "/bin");
+        env.export("PS1", DEFAULT_PS1);
+        env
+    }
+
+    /// Get the value of a variable
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(|var| var.value.as_str())
+    }
+
+    /// Set a variable, keeping it exported if it already was
+    pub fn set(&mut self, name: &str, value: &str) {
+        match self.variables.get_mut(name) {
+            Some(var) => var.value = String::from(value),
+            None => {
+                self.variables.insert(String::from(name), Variable { value: String::from(value), exported: false });
+            }
+        }
+    }
+
+    /// Set a variable and add it to the environment
+    pub fn export(&mut self, name: &str, value: &str) {
+        self.set(name, value);
+        self.mark_exported(name);
+    }
+
+    /// Add an existing variable to the environment, creating it empty if needed
+    pub fn mark_exported(&mut self, name: &str) {
+        self.variables
+            .entry(String::from(name))
+            .or_insert_with(|| Variable { value: String::new(), exported: false })
+            .exported = true;
+    }
+
+    /// Remove a variable; returns whether it existed
+    pub fn unset(&mut self, name: &str) -> bool {
+        self.variables.remove(name).is_some()
+    }
+
+    /// All variables with their values, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.variables.iter().map(|(name, var)| (name.as_str(), var.value.as_str()))
+    }
+
+    /// Exported variables with their values, sorted by name
+    pub fn exported(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.variables
+            .iter()
+            .filter(|(_, var)| var.exported)
+            .map(|(name, var)| (name.as_str(), var.value.as_str()))
+    }
+}
+
+/// Prompt used when `PS1` is not set: the working directory followed by `$`
+pub const DEFAULT_PS1: &str = "\\w $ ";
+
+/// Split a `NAME=value` assignment; returns None if the word is not one
+pub fn parse_assignment(word: &str) -> Option<(&str, &str)> {
+    let (name, value) = word.split_once('=')?;
+    if is_valid_name(name) {
+        Some((name, value))
+    } else {
+        None
+    }
+}
+
+/// Expand the escapes of a prompt string
+///
+/// `\w` is the working directory, `\W` its last component, `\$` a dollar sign,
+/// `\n` a new line and `\\` a backslash; other characters are kept as they are.
+pub fn expand_prompt(ps1: &str, cwd: &str) -> String {
+    let mut prompt = String::new();
+    let mut chars = ps1.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            prompt.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('w') => prompt.push_str(cwd),
+            Some('W') => {
+                let name = cwd.rsplit('/').next().filter(|name| !name.is_empty()).unwrap_or("/");
+                prompt.push_str(name);
+            }
+            Some('$') => prompt.push('$'),
+            Some('n') => prompt.push('\n'),
+            Some('\\') => prompt.push('\\'),
+            Some(other) => {
+                prompt.push('\\');
+                prompt.push(other);
+            }
+            None => prompt.push('\\'),
+        }
+    }
+    prompt
+}
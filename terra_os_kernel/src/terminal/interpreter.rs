@@ -0,0 +1,463 @@
+//! Shell interpreter: runs parsed command lines and scripts on a terminal session
+//!
+//! Every command returns an exit status, 0 meaning success; `$?` holds the status of the
+//! last pipeline. Scripts run in the current session, so the variables and functions they
+//! define stay set after they finish.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use super::commands;
+use super::env;
+use super::io::{Context, Stdin, Stdout};
+use super::parser::{self, AndOr, Command, CommandKind, Connector, List, Pipeline};
+use crate::fs::file::OpenFlags;
+
+/// Maximum nesting of function calls and scripts, so runaway recursion cannot exhaust the kernel stack
+pub const MAX_CALL_DEPTH: usize = 16;
+
+/// Status of a command that could not be found
+pub const STATUS_NOT_FOUND: i32 = 127;
+
+/// How `break`, `continue`, `return` and `exit` unwind the commands being run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Leave this many enclosing loops
+    Break(usize),
+    /// Leave this many minus one enclosing loops and continue the next iteration of the last one
+    Continue(usize),
+    /// Leave the running function or script
+    Return,
+    /// Leave the running script
+    Exit,
+}
+
+/// Interpreter state of a terminal session
+pub struct Interpreter {
+    functions: BTreeMap<String, Rc<Command>>,
+    // 当前脚本或函数的位置参数，下标0是$0
+    positional: Vec<String>,
+    last_status: i32,
+    // 等待处理的控制流跳转
+    flow: Option<Flow>,
+    // 正在执行的循环层数，进入函数或脚本时从0重新计数
+    loop_depth: usize,
+    // 函数调用和脚本的嵌套层数
+    call_depth: usize,
+    // 正在执行的脚本层数
+    script_depth: usize,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter {
+            functions: BTreeMap::new(),
+            positional: alloc::vec![String::from("sh")],
+            last_status: 0,
+            flow: None,
+            loop_depth: 0,
+            call_depth: 0,
+            script_depth: 0,
+        }
+    }
+
+    /// Exit status of the last pipeline
+    pub fn last_status(&self) -> i32 {
+        self.last_status
+    }
+
+    /// Value of a special parameter: `$?`, `$#` or a positional parameter `$0`, `$1`...
+    pub fn parameter(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => Some(self.last_status.to_string()),
+            "#" => Some((self.positional.len() - 1).to_string()),
+            _ => {
+                let index: usize = name.parse().ok()?;
+                self.positional.get(index).cloned()
+            }
+        }
+    }
+
+    /// Number of loops around the running command in the current function or script
+    pub fn loop_depth(&self) -> usize {
+        self.loop_depth
+    }
+
+    /// Whether a function or script is running, so `return` is allowed
+    pub fn in_call(&self) -> bool {
+        self.call_depth > 0
+    }
+
+    /// Whether a script is running, so `exit` is allowed
+    pub fn in_script(&self) -> bool {
+        self.script_depth > 0
+    }
+
+    /// Start unwinding the running commands; used by `break`, `continue`, `return` and `exit`
+    pub fn set_flow(&mut self, flow: Flow) {
+        self.flow = Some(flow);
+    }
+}
+
+/// Parse and run a command line or the text of a script
+pub fn run_source(ctx: &mut Context, source: &str) -> i32 {
+    let status = match parser::parse(source) {
+        Ok(list) => run_list(ctx, &list),
+        Err(e) => {
+            ctx.write_err(&format!("sh: {}\n", e));
+            2
+        }
+    };
+    ctx.terminal.interpreter.last_status = status;
+    status
+}
+
+/// Run a script file with `$0` set to `path` and `$1`... to `args`
+pub fn run_script(ctx: &mut Context, path: &str, args: &[&str]) -> i32 {
+    use crate::fs::FsError;
+
+    let resolved = ctx.terminal.resolve_path(path);
+    let source = match ctx.terminal.read_file(&resolved) {
+        Ok(data) => String::from_utf8_lossy(&data).into_owned(),
+        Err(e) => {
+            ctx.print_fs_error("sh", path, e);
+            return if e == FsError::NotFound { STATUS_NOT_FOUND } else { 126 };
+        }
+    };
+
+    let parameters = core::iter::once(path).chain(args.iter().copied()).map(String::from).collect();
+    let saved = match enter_call(ctx, path, parameters) {
+        Some(saved) => saved,
+        None => return 1,
+    };
+    ctx.terminal.interpreter.script_depth += 1;
+    let status = run_source(ctx, &source);
+    ctx.terminal.interpreter.script_depth -= 1;
+    leave_call(ctx, saved);
+
+    // exit和return都只结束这个脚本
+    let interpreter = &mut ctx.terminal.interpreter;
+    if matches!(interpreter.flow, Some(Flow::Return | Flow::Exit)) {
+        interpreter.flow = None;
+    }
+    status
+}
+
+// 进入函数或脚本时保存的状态
+struct SavedCall {
+    positional: Vec<String>,
+    loop_depth: usize,
+}
+
+fn enter_call(ctx: &mut Context, name: &str, positional: Vec<String>) -> Option<SavedCall> {
+    if ctx.terminal.interpreter.call_depth >= MAX_CALL_DEPTH {
+        ctx.write_err(&format!("sh: {}: maximum nesting depth exceeded\n", name));
+        return None;
+    }
+    let interpreter = &mut ctx.terminal.interpreter;
+    interpreter.call_depth += 1;
+    Some(SavedCall {
+        positional: core::mem::replace(&mut interpreter.positional, positional),
+        loop_depth: core::mem::take(&mut interpreter.loop_depth),
+    })
+}
+
+fn leave_call(ctx: &mut Context, saved: SavedCall) {
+    let interpreter = &mut ctx.terminal.interpreter;
+    interpreter.call_depth -= 1;
+    interpreter.positional = saved.positional;
+    interpreter.loop_depth = saved.loop_depth;
+}
+
+fn run_list(ctx: &mut Context, list: &List) -> i32 {
+    let mut status = ctx.terminal.interpreter.last_status;
+    for and_or in list {
+        status = run_and_or(ctx, and_or);
+        if ctx.terminal.interpreter.flow.is_some() {
+            break;
+        }
+    }
+    status
+}
+
+fn run_and_or(ctx: &mut Context, and_or: &AndOr) -> i32 {
+    let mut status = run_pipeline(ctx, &and_or.first);
+    for (connector, pipeline) in &and_or.rest {
+        if ctx.terminal.interpreter.flow.is_some() {
+            break;
+        }
+        let run = match connector {
+            Connector::And => status == 0,
+            Connector::Or => status != 0,
+        };
+        if run {
+            status = run_pipeline(ctx, pipeline);
+        }
+    }
+    status
+}
+
+fn run_pipeline(ctx: &mut Context, pipeline: &Pipeline) -> i32 {
+    // 命令依次执行，前一个命令的全部输出作为后一个命令的输入
+    let mut piped = Vec::new();
+    let mut status = 0;
+    for (i, command) in pipeline.iter().enumerate() {
+        let mut stdin = Stdin::Pipe { data: core::mem::take(&mut piped), position: 0 };
+        let mut stdout = Stdout::Pipe(Vec::new());
+        let first = i == 0;
+        let last = i + 1 == pipeline.len();
+        {
+            let mut stage = ctx.redirect(
+                if first { None } else { Some(&mut stdin) },
+                if last { None } else { Some(&mut stdout) },
+            );
+            status = run_command(&mut stage, command);
+        }
+        if let Stdout::Pipe(data) = stdout {
+            piped = data;
+        }
+    }
+    ctx.terminal.interpreter.last_status = status;
+    status
+}
+
+fn run_command(ctx: &mut Context, command: &Command) -> i32 {
+    let mut stdin = None;
+    if let Some(path) = &command.input {
+        match open_redirect(ctx, path, OpenFlags::READ) {
+            Some(fd) => stdin = Some(Stdin::File(fd)),
+            None => return 1,
+        }
+    }
+    let mut stdout = None;
+    if let Some(redirect) = &command.output {
+        let mode = if redirect.append { OpenFlags::APPEND } else { OpenFlags::TRUNCATE };
+        match open_redirect(ctx, &redirect.path, OpenFlags::WRITE | OpenFlags::CREATE | mode) {
+            Some(fd) => stdout = Some(Stdout::File { fd, error: None }),
+            None => {
+                if let Some(Stdin::File(fd)) = stdin {
+                    let _ = ctx.terminal.files().close(fd);
+                }
+                return 1;
+            }
+        }
+    }
+
+    let mut status = {
+        let mut inner = ctx.redirect(stdin.as_mut(), stdout.as_mut());
+        run_kind(&mut inner, &command.kind)
+    };
+
+    if let Some(Stdin::File(fd)) = stdin {
+        let _ = ctx.terminal.files().close(fd);
+    }
+    if let (Some(Stdout::File { fd, error }), Some(redirect)) = (stdout, &command.output) {
+        let _ = ctx.terminal.files().close(fd);
+        if let Some(e) = error {
+            ctx.write_err(&format!("sh: {}: {}\n", redirect.path, e));
+            status = 1;
+        }
+    }
+    status
+}
+
+/// Expand the file name of a redirection and open it, reporting an error if that fails
+fn open_redirect(ctx: &mut Context, raw: &str, flags: OpenFlags) -> Option<crate::fs::file::Fd> {
+    let path = match expand(ctx, raw) {
+        Ok(Some(path)) => path,
+        Ok(None) => {
+            ctx.write_err(&format!("sh: {}: ambiguous redirect\n", raw));
+            return None;
+        }
+        Err(e) => {
+            ctx.write_err(&format!("sh: {}\n", e));
+            return None;
+        }
+    };
+    let resolved = ctx.terminal.resolve_path(&path);
+    match ctx.terminal.files().open(&resolved, flags) {
+        Ok(fd) => Some(fd),
+        Err(e) => {
+            ctx.write_err(&format!("sh: {}: {}\n", path, e));
+            None
+        }
+    }
+}
+
+fn expand(ctx: &Context, raw: &str) -> Result<Option<String>, parser::ParseError> {
+    parser::expand_word(raw, |name| ctx.terminal.variable(name))
+}
+
+// 展开一组单词，展开为空的未加引号单词被丢弃
+fn expand_words(ctx: &mut Context, raw: &[String]) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    for word in raw {
+        match expand(ctx, word) {
+            Ok(Some(word)) => words.push(word),
+            Ok(None) => {}
+            Err(e) => {
+                ctx.write_err(&format!("sh: {}\n", e));
+                return None;
+            }
+        }
+    }
+    Some(words)
+}
+
+fn run_kind(ctx: &mut Context, kind: &CommandKind) -> i32 {
+    match kind {
+        CommandKind::Simple(words) => run_simple(ctx, words),
+        CommandKind::Group(list) => run_list(ctx, list),
+        CommandKind::If { branches, otherwise } => {
+            for (condition, body) in branches {
+                let status = run_list(ctx, condition);
+                if ctx.terminal.interpreter.flow.is_some() {
+                    return status;
+                }
+                if status == 0 {
+                    return run_list(ctx, body);
+                }
+            }
+            match otherwise {
+                Some(body) => run_list(ctx, body),
+                None => 0,
+            }
+        }
+        CommandKind::Loop { condition, body, until } => {
+            let mut status = 0;
+            ctx.terminal.interpreter.loop_depth += 1;
+            loop {
+                let test = run_list(ctx, condition);
+                if end_iteration(ctx) || (test == 0) == *until {
+                    break;
+                }
+                status = run_list(ctx, body);
+                if end_iteration(ctx) {
+                    break;
+                }
+            }
+            ctx.terminal.interpreter.loop_depth -= 1;
+            status
+        }
+        CommandKind::For { variable, words, body } => {
+            let values = match words {
+                Some(words) => match expand_words(ctx, words) {
+                    Some(values) => values,
+                    None => return 1,
+                },
+                None => ctx.terminal.interpreter.positional[1..].to_vec(),
+            };
+
+            let mut status = 0;
+            ctx.terminal.interpreter.loop_depth += 1;
+            for value in values {
+                ctx.terminal.env.set(variable, &value);
+                status = run_list(ctx, body);
+                if end_iteration(ctx) {
+                    break;
+                }
+            }
+            ctx.terminal.interpreter.loop_depth -= 1;
+            status
+        }
+        CommandKind::Function { name, body } => {
+            ctx.terminal.interpreter.functions.insert(name.clone(), body.clone());
+            0
+        }
+    }
+}
+
+/// Handle a pending `break` or `continue` after a loop iteration; returns whether the loop ends
+fn end_iteration(ctx: &mut Context) -> bool {
+    let interpreter = &mut ctx.terminal.interpreter;
+    match interpreter.flow {
+        Some(Flow::Break(levels)) => {
+            interpreter.flow = if levels > 1 { Some(Flow::Break(levels - 1)) } else { None };
+            true
+        }
+        Some(Flow::Continue(levels)) if levels > 1 => {
+            interpreter.flow = Some(Flow::Continue(levels - 1));
+            true
+        }
+        Some(Flow::Continue(_)) => {
+            interpreter.flow = None;
+            false
+        }
+        Some(Flow::Return | Flow::Exit) => true,
+        None => false,
+    }
+}
+
+fn run_simple(ctx: &mut Context, words: &[String]) -> i32 {
+    let words = match expand_words(ctx, words) {
+        Some(words) => words,
+        None => return 1,
+    };
+
+    // 只由NAME=value组成的命令设置shell变量
+    let assignments: Option<Vec<(&str, &str)>> = words.iter().map(|word| env::parse_assignment(word)).collect();
+    if let Some(assignments) = assignments {
+        for (name, value) in assignments {
+            ctx.terminal.env.set(name, value);
+        }
+        return 0;
+    }
+
+    let argv: Vec<&str> = words.iter().map(|word| word.as_str()).collect();
+    let name = argv[0];
+
+    // 查找顺序：函数、内建命令、PATH中的脚本
+    let function = ctx.terminal.interpreter.functions.get(name).cloned();
+    if let Some(body) = function {
+        return call_function(ctx, &body, &argv);
+    }
+    if let Some(cmd) = commands::find_command(name) {
+        return cmd.execute(ctx, &argv);
+    }
+    match find_script(ctx, name) {
+        Some(path) => run_script(ctx, &path, &argv[1..]),
+        None => {
+            ctx.write_err(&format!("Unknown command: {}\n", name));
+            STATUS_NOT_FOUND
+        }
+    }
+}
+
+fn call_function(ctx: &mut Context, body: &Command, argv: &[&str]) -> i32 {
+    // $0保持不变，$1...换成函数的参数
+    let mut positional = Vec::with_capacity(argv.len());
+    positional.push(ctx.terminal.interpreter.positional[0].clone());
+    positional.extend(argv[1..].iter().map(|arg| String::from(*arg)));
+
+    let saved = match enter_call(ctx, argv[0], positional) {
+        Some(saved) => saved,
+        None => return 1,
+    };
+    let status = run_command(ctx, body);
+    leave_call(ctx, saved);
+
+    let interpreter = &mut ctx.terminal.interpreter;
+    if interpreter.flow == Some(Flow::Return) {
+        interpreter.flow = None;
+    }
+    status
+}
+
+/// Find a script for a command name: a path if it contains '/', otherwise a file in `$PATH`
+fn find_script(ctx: &mut Context, name: &str) -> Option<String> {
+    use crate::fs::new_fs::FileSystem;
+
+    if name.contains('/') {
+        return Some(String::from(name));
+    }
+    let vfs = crate::fs::vfs().ok()?;
+    let search_path = String::from(ctx.terminal.env.get("PATH")?);
+    search_path
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| ctx.terminal.resolve_path(&format!("{}/{}", dir, name)))
+        .find(|path| vfs.metadata(path).map(|meta| !meta.is_dir).unwrap_or(false))
+}
@@ -0,0 +1,160 @@
+//! Standard input and output of shell commands
+//!
+//! Commands read and write through a [`Context`] instead of the terminal itself, so the
+//! shell can connect them to a pipe or to a file. Error messages always go to the terminal.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::Terminal;
+use crate::fs::file::Fd;
+use crate::fs::FsError;
+
+/// Number of bytes read from the input at a time by `read_to_end`
+const READ_CHUNK_SIZE: usize = 512;
+
+/// Where a command reads its input from
+pub enum Stdin {
+    /// The keyboard; it is not a byte stream, so reading it gives end of input
+    Terminal,
+    /// Output of the previous command of a pipeline
+    Pipe { data: Vec<u8>, position: usize },
+    /// A file opened for `< file`
+    File(Fd),
+}
+
+/// Where a command writes its output to
+pub enum Stdout {
+    /// The terminal screen
+    Terminal,
+    /// Collected for the next command of a pipeline
+    Pipe(Vec<u8>),
+    /// A file opened for `> file` or `>> file`; after the first write error the output is
+    /// dropped and the error kept for the shell to report
+    File { fd: Fd, error: Option<FsError> },
+}
+
+/// The terminal session a command runs in, together with its standard streams
+pub struct Context<'a> {
+    /// The terminal session, for the working directory, open files and settings
+    pub terminal: &'a mut Terminal,
+    stdin: &'a mut Stdin,
+    stdout: &'a mut Stdout,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(terminal: &'a mut Terminal, stdin: &'a mut Stdin, stdout: &'a mut Stdout) -> Self {
+        Context { terminal, stdin, stdout }
+    }
+
+    /// A context for a nested command that replaces some of these streams
+    pub fn redirect<'b>(&'b mut self, stdin: Option<&'b mut Stdin>, stdout: Option<&'b mut Stdout>) -> Context<'b> {
+        Context {
+            terminal: &mut *self.terminal,
+            stdin: stdin.unwrap_or(&mut *self.stdin),
+            stdout: stdout.unwrap_or(&mut *self.stdout),
+        }
+    }
+
+    /// Whether the input comes from the keyboard rather than a pipe or a file
+    pub fn stdin_is_terminal(&self) -> bool {
+        matches!(self.stdin, Stdin::Terminal)
+    }
+
+    /// Read up to `length` bytes of input; an empty result means the end of the input
+    pub fn read(&mut self, length: usize) -> Result<Vec<u8>, FsError> {
+        match &mut *self.stdin {
+            Stdin::Terminal => Ok(Vec::new()),
+            Stdin::Pipe { data, position } => {
+                let end = (*position + length).min(data.len());
+                let chunk = data[*position..end].to_vec();
+                *position = end;
+                Ok(chunk)
+            }
+            Stdin::File(fd) => self.terminal.files().read(*fd, length as u64),
+        }
+    }
+
+    /// Read the rest of the input
+    pub fn read_to_end(&mut self) -> Result<Vec<u8>, FsError> {
+        let mut data = Vec::new();
+        loop {
+            let chunk = self.read(READ_CHUNK_SIZE)?;
+            if chunk.is_empty() {
+                return Ok(data);
+            }
+            data.extend_from_slice(&chunk);
+        }
+    }
+
+    /// Read one line of input without its newline; `None` at the end of the input
+    ///
+    /// The keyboard is only read at the interactive prompt: a script whose input is the
+    /// terminal gets the end of input instead of waiting for a key.
+    pub fn read_line(&mut self) -> Option<String> {
+        if self.stdin_is_terminal() {
+            if self.terminal.interpreter.in_script() {
+                return None;
+            }
+            return Some(self.terminal.read_line());
+        }
+
+        let mut line = Vec::new();
+        loop {
+            match self.read(1).ok().and_then(|byte| byte.first().copied()) {
+                Some(b'\n') => break,
+                Some(byte) => line.push(byte),
+                None if line.is_empty() => return None,
+                None => break,
+            }
+        }
+        Some(String::from_utf8_lossy(&line).into_owned())
+    }
+
+    /// Write bytes to the output
+    pub fn write_bytes(&mut self, data: &[u8]) {
+        match &mut *self.stdout {
+            Stdout::Terminal => {
+                for &byte in data {
+                    self.terminal.write_byte(byte);
+                }
+            }
+            Stdout::Pipe(buffer) => buffer.extend_from_slice(data),
+            Stdout::File { fd, error } => {
+                if error.is_none() {
+                    if let Err(e) = self.terminal.files().write(*fd, data) {
+                        *error = Some(e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write a string to the output
+    pub fn write_str(&mut self, s: &str) {
+        self.write_bytes(s.as_bytes());
+    }
+
+    /// Write a byte to the output
+    pub fn write_byte(&mut self, byte: u8) {
+        self.write_bytes(&[byte]);
+    }
+
+    /// Write an error or usage message to the terminal, bypassing any redirection
+    pub fn write_err(&mut self, s: &str) {
+        self.terminal.write_str(s);
+    }
+
+    /// Report a filesystem error on the terminal in the shell's `command: action: message` form
+    pub fn print_fs_error(&mut self, command: &str, action: &str, error: FsError) {
+        self.terminal.print_fs_error(command, action, error);
+    }
+}
+
+impl fmt::Write for Context<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        Context::write_str(self, s);
+        Ok(())
+    }
+}
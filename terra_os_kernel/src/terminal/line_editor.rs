@@ -0,0 +1,286 @@
+//! Line editing state for the terminal: the line being edited and the command history
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// Default number of commands kept in the history
+pub const DEFAULT_HISTORY_DEPTH: usize = 32;
+
+/// The line being edited, with a cursor position counted in characters
+pub struct LineBuffer {
+    chars: Vec<char>,
+    cursor: usize,
+}
+
+impl LineBuffer {
+    pub fn new() -> Self {
+        Self {
+            chars: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    pub fn chars(&self) -> &[char] {
+        &self.chars
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// Replace the whole line, leaving the cursor at the end
+    pub fn set(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+    }
+
+    /// Insert a character at the cursor
+    pub fn insert(&mut self, c: char) {
+        self.chars.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    /// Delete the character before the cursor
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.chars.remove(self.cursor);
+        }
+    }
+
+    /// Delete the character under the cursor
+    pub fn delete(&mut self) {
+        if self.cursor < self.chars.len() {
+            self.chars.remove(self.cursor);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.chars.len());
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.chars.len();
+    }
+
+    /// Delete from the cursor to the end of the line (Ctrl-K)
+    pub fn kill_to_end(&mut self) {
+        self.chars.truncate(self.cursor);
+    }
+
+    /// Delete from the start of the line to the cursor (Ctrl-U)
+    pub fn kill_to_start(&mut self) {
+        self.chars.drain(..self.cursor);
+        self.cursor = 0;
+    }
+
+    /// Delete the word before the cursor, along with any spaces after it (Ctrl-W)
+    pub fn delete_word(&mut self) {
+        let start = self.word_start();
+        self.chars.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    /// Delete from the cursor to the end of the next word (Alt-D)
+    pub fn delete_word_forward(&mut self) {
+        let end = self.word_end();
+        self.chars.drain(self.cursor..end);
+    }
+
+    /// Move to the start of the word before the cursor (Alt-B)
+    pub fn word_left(&mut self) {
+        self.cursor = self.word_start();
+    }
+
+    /// Move past the end of the next word (Alt-F)
+    pub fn word_right(&mut self) {
+        self.cursor = self.word_end();
+    }
+
+    // Start of the word before the cursor, skipping spaces right before it
+    fn word_start(&self) -> usize {
+        let mut start = self.cursor;
+        while start > 0 && self.chars[start - 1] == ' ' {
+            start -= 1;
+        }
+        while start > 0 && self.chars[start - 1] != ' ' {
+            start -= 1;
+        }
+        start
+    }
+
+    // End of the word after the cursor, skipping spaces right after it
+    fn word_end(&self) -> usize {
+        let mut end = self.cursor;
+        while end < self.chars.len() && self.chars[end] == ' ' {
+            end += 1;
+        }
+        while end < self.chars.len() && self.chars[end] != ' ' {
+            end += 1;
+        }
+        end
+    }
+}
+
+impl From<&LineBuffer> for String {
+    fn from(line: &LineBuffer) -> String {
+        line.chars.iter().collect()
+    }
+}
+
+/// Command history, oldest entry first
+pub struct History {
+    entries: VecDeque<String>,
+    depth: usize,
+}
+
+impl History {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            depth,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Change the maximum number of entries, dropping the oldest ones if needed
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        while self.entries.len() > depth {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Record a command; blank lines and repeats of the last command are skipped
+    pub fn push(&mut self, line: &str) {
+        if line.trim().is_empty() || self.entries.back().map(|last| last == line).unwrap_or(false) {
+            return;
+        }
+        if self.depth == 0 {
+            return;
+        }
+        if self.entries.len() == self.depth {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(String::from(line));
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|entry| entry.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.as_str())
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str, cursor: usize) -> LineBuffer {
+        let mut line = LineBuffer::new();
+        line.set(text);
+        line.cursor = cursor;
+        line
+    }
+
+    fn text(line: &LineBuffer) -> String {
+        String::from(line)
+    }
+
+    #[test_case]
+    fn insert_and_delete_at_the_cursor() {
+        let mut line = line("ac", 1);
+        line.insert('b');
+        assert_eq!((text(&line).as_str(), line.cursor()), ("abc", 2));
+
+        line.delete();
+        assert_eq!((text(&line).as_str(), line.cursor()), ("ab", 2));
+        // Nothing under the cursor at the end of the line
+        line.delete();
+        assert_eq!(text(&line), "ab");
+
+        line.backspace();
+        line.home();
+        line.backspace();
+        assert_eq!((text(&line).as_str(), line.cursor()), ("a", 0));
+    }
+
+    #[test_case]
+    fn cursor_stays_inside_the_line() {
+        let mut line = line("ab", 0);
+        line.move_left();
+        assert_eq!(line.cursor(), 0);
+        line.end();
+        line.move_right();
+        assert_eq!(line.cursor(), 2);
+    }
+
+    #[test_case]
+    fn kill_to_end_and_start() {
+        let mut line = line("hello world", 5);
+        line.kill_to_end();
+        assert_eq!((text(&line).as_str(), line.cursor()), ("hello", 5));
+
+        let mut line = self::line("hello world", 6);
+        line.kill_to_start();
+        assert_eq!((text(&line).as_str(), line.cursor()), ("world", 0));
+    }
+
+    #[test_case]
+    fn word_editing() {
+        let mut line = line("ls  foo bar", 8);
+        line.delete_word();
+        assert_eq!((text(&line).as_str(), line.cursor()), ("ls  bar", 4));
+
+        line.word_left();
+        assert_eq!(line.cursor(), 0);
+        line.word_right();
+        assert_eq!(line.cursor(), 2);
+        line.delete_word_forward();
+        assert_eq!((text(&line).as_str(), line.cursor()), ("ls", 2));
+    }
+
+    #[test_case]
+    fn history_skips_blanks_and_repeats() {
+        let mut history = History::new(2);
+        history.push("a");
+        history.push("a");
+        history.push("  ");
+        history.push("b");
+        history.push("c");
+        assert_eq!(history.iter().collect::<Vec<_>>(), ["b", "c"]);
+
+        history.set_depth(1);
+        assert_eq!(history.iter().collect::<Vec<_>>(), ["c"]);
+    }
+}
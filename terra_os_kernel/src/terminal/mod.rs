@@ -1,52 +1,25 @@
 //! A simple terminal implementation for TerraOS
 
-mod commands;
+pub mod commands;
+mod completion;
+mod env;
+mod interpreter;
+pub mod io;
+mod line_editor;
+mod parser;
+mod scrollback;
 
 use core::fmt;
-use heapless::Vec;
-use x86_64::instructions::port::Port;
+use alloc::boxed::Box;
 use alloc::string::String;
+use alloc::vec::Vec;
 use alloc::format;
 
-// 键盘扫描码到ASCII字符的映射
-const SCANCODE_TO_ASCII: [u8; 128] = [
-    0,  0,  '1' as u8, '2' as u8, '3' as u8, '4' as u8, '5' as u8, '6' as u8,
-    '7' as u8, '8' as u8, '9' as u8, '0' as u8, '-' as u8, '=' as u8, 0x08, 0x09,
-    'q' as u8, 'w' as u8, 'e' as u8, 'r' as u8, 't' as u8, 'y' as u8, 'u' as u8, 'i' as u8,
-    'o' as u8, 'p' as u8, '[' as u8, ']' as u8, 0x0D,  0,  'a' as u8, 's' as u8,
-    'd' as u8, 'f' as u8, 'g' as u8, 'h' as u8, 'j' as u8, 'k' as u8, 'l' as u8, ';' as u8,
-    '\'' as u8, '`' as u8,  0,  '\\' as u8, 'z' as u8, 'x' as u8, 'c' as u8, 'v' as u8,
-    'b' as u8, 'n' as u8, 'm' as u8, ',' as u8, '.' as u8, '/' as u8,  0,  '*' as u8,
-    0,  ' ' as u8,  0,  0,  0,  0,  0,  0,
-    0,  0,  0,  0,  0,  0,  0,  '7' as u8,
-    '8' as u8, '9' as u8, '-' as u8, '4' as u8, '5' as u8, '6' as u8, '+' as u8, '1' as u8,
-    '2' as u8, '3' as u8, '0' as u8, '.' as u8,  0,  0,  0,  0,
-    0,  0,  0,  0,  0,  0,  0,  0,
-    0,  0,  0,  0,  0,  0,  0,  0,
-    0,  0,  0,  0,  0,  0,  0,  0,
-    0,  0,  0,  0,  0,  0,  0,  0,
-    0,  0,  0,  0,  0,  0,  0,  0,
-];
-
-// 大写字母映射
-const SCANCODE_TO_UPPERCASE: [u8; 128] = [
-    0,  0,  '!' as u8, '@' as u8, '#' as u8, '$' as u8, '%' as u8, '^' as u8,
-    '&' as u8, '*' as u8, '(' as u8, ')' as u8, '_' as u8, '+' as u8, 0x08, 0x09,
-    'Q' as u8, 'W' as u8, 'E' as u8, 'R' as u8, 'T' as u8, 'Y' as u8, 'U' as u8, 'I' as u8,
-    'O' as u8, 'P' as u8, '{' as u8, '}' as u8, 0x0D,  0,  'A' as u8, 'S' as u8,
-    'D' as u8, 'F' as u8, 'G' as u8, 'H' as u8, 'J' as u8, 'K' as u8, 'L' as u8, ':' as u8,
-    '"' as u8, '~' as u8,  0,  '|' as u8, 'Z' as u8, 'X' as u8, 'C' as u8, 'V' as u8,
-    'B' as u8, 'N' as u8, 'M' as u8, '<' as u8, '>' as u8, '?' as u8,  0,  '*' as u8,
-    0,  ' ' as u8,  0,  0,  0,  0,  0,  0,
-    0,  0,  0,  0,  0,  0,  0,  '7' as u8,
-    '8' as u8, '9' as u8, '-' as u8, '4' as u8, '5' as u8, '6' as u8, '+' as u8, '1' as u8,
-    '2' as u8, '3' as u8, '0' as u8, '.' as u8,  0,  0,  0,  0,
-    0,  0,  0,  0,  0,  0,  0,  0,
-    0,  0,  0,  0,  0,  0,  0,  0,
-    0,  0,  0,  0,  0,  0,  0,  0,
-    0,  0,  0,  0,  0,  0,  0,  0,
-    0,  0,  0,  0,  0,  0,  0,  0,
-];
+use self::env::Environment;
+use self::interpreter::Interpreter;
+use self::io::{Context, Stdin, Stdout};
+use self::line_editor::{History, LineBuffer, DEFAULT_HISTORY_DEPTH};
+use self::scrollback::{Scrollback, DEFAULT_SCROLLBACK_LINES};
 
 /// The VGA text buffer color codes
 #[allow(dead_code)]
@@ -77,7 +50,7 @@ pub enum Color {
 pub struct ColorCode(u8);
 
 impl ColorCode {
-    pub fn new(foreground: Color, background: Color) -> Self {
+    pub const fn new(foreground: Color, background: Color) -> Self {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
 }
@@ -100,39 +73,101 @@ struct Buffer {
     chars: [[ScreenChar; BUFFER_WIDTH]; BUFFER_HEIGHT],
 }
 
+/// An empty cell of the screen
+const BLANK: ScreenChar = ScreenChar {
+    ascii_character: b' ',
+    color_code: ColorCode::new(Color::Black, Color::Black),
+};
+
+/// Script run when the terminal starts, if it exists
+const STARTUP_SCRIPT: &str = "/etc/rc";
+
+/// Minimum number of columns left for input after the prompt
+const MIN_INPUT_WIDTH: usize = 16;
+
 /// The terminal struct
 pub struct Terminal {
     column_position: usize,
     color_code: ColorCode,
-    buffer: &'static mut Buffer,
-    shift_pressed: bool,
+    // 屏幕内容，查看回滚历史时照常接收输出
+    buffer: Box<Buffer>,
+    // VGA文本缓冲区，显示buffer或者回滚到的历史
+    vga: &'static mut Buffer,
+    // 滚出屏幕顶部的行，Shift+PageUp/PageDown浏览
+    scrollback: Scrollback,
+    // 键盘扫描码解码器
+    keyboard: crate::keyboard::Decoder,
     // 全局分配器引用，用于内存监控
     allocator: &'static crate::allocator::LinkedListAllocator,
+    // 本终端会话的打开文件表
+    files: crate::fs::file::FileTable,
+    // 当前工作目录，总是规范化的绝对路径
+    cwd: String,
+    // 命令历史，read_line中用上下方向键浏览
+    history: History,
+    // shell变量，PWD随cwd更新
+    env: Environment,
+    // 脚本解释器状态：函数、位置参数和$?
+    interpreter: Interpreter,
 }
 
 impl Terminal {
     /// Create a new terminal instance
     pub fn new(allocator: &'static crate::allocator::LinkedListAllocator) -> Self {
+        let vga = unsafe { &mut *(0xb8000 as *mut Buffer) };
         Terminal {
             column_position: 0,
             color_code: ColorCode::new(Color::Green, Color::Black),
-            buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-            shift_pressed: false,
+            // 从屏幕上已有的内容开始
+            buffer: Box::new(Buffer { chars: vga.chars }),
+            vga,
+            scrollback: Scrollback::new(DEFAULT_SCROLLBACK_LINES),
+            keyboard: crate::keyboard::Decoder::new(),
             allocator,
+            files: crate::fs::file::FileTable::new(),
+            cwd: String::from("/"),
+            history: History::new(DEFAULT_HISTORY_DEPTH),
+            env: Environment::new(),
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    /// Get the open-file table of this terminal session
+    pub fn files(&mut self) -> &mut crate::fs::file::FileTable {
+        &mut self.files
+    }
+
+    /// Get the current working directory
+    pub fn cwd(&self) -> &str {
+        &self.cwd
+    }
+
+    /// Resolve a path argument against the current working directory
+    pub fn resolve_path(&self, path: &str) -> String {
+        crate::fs::resolve_path(&self.cwd, path)
+    }
+
+    /// Change the current working directory; the target must be an existing directory
+    pub fn change_directory(&mut self, path: &str) -> Result<(), crate::fs::FsError> {
+        use crate::fs::new_fs::FileSystem;
+        let path = self.resolve_path(path);
+        if !crate::fs::vfs()?.metadata(&path)?.is_dir {
+            return Err(crate::fs::FsError::NotADirectory);
         }
+        self.env.set("PWD", &path);
+        self.cwd = path;
+        Ok(())
     }
 
     /// Clear the terminal screen
     pub fn clear(&mut self) {
         for row in 0..BUFFER_HEIGHT {
             for col in 0..BUFFER_WIDTH {
-                self.buffer.chars[row][col] = ScreenChar {
-                    ascii_character: b' ',
-                    color_code: ColorCode::new(Color::Black, Color::Black),
-                };
+                self.buffer.chars[row][col] = BLANK;
             }
         }
         self.column_position = 0;
+        self.redraw();
     }
 
     /// Write a byte to the terminal
@@ -147,18 +182,24 @@ impl Terminal {
                 let row = BUFFER_HEIGHT - 1;
                 let col = self.column_position;
 
-                self.buffer.chars[row][col] = ScreenChar {
+                self.set_char(row, col, ScreenChar {
                     ascii_character: byte,
                     color_code: self.color_code,
-                };
+                });
                 self.column_position += 1;
             }
         }
     }
 
+    /// Write a character, translating it to the VGA code page 437
+    pub fn write_char(&mut self, c: char) {
+        self.write_byte(to_cp437(c));
+    }
+
     /// Move to a new line
     fn new_line(&mut self) {
-        // Scroll up all lines
+        // Keep the top line in the scrollback, then scroll up all lines
+        self.scrollback.push(self.buffer.chars[0]);
         for row in 1..BUFFER_HEIGHT {
             for col in 0..BUFFER_WIDTH {
                 self.buffer.chars[row - 1][col] = self.buffer.chars[row][col];
@@ -167,13 +208,49 @@ impl Terminal {
 
         // Clear the last line
         for col in 0..BUFFER_WIDTH {
-            self.buffer.chars[BUFFER_HEIGHT - 1][col] = ScreenChar {
-                ascii_character: b' ',
-                color_code: ColorCode::new(Color::Black, Color::Black),
-            };
+            self.buffer.chars[BUFFER_HEIGHT - 1][col] = BLANK;
         }
 
         self.column_position = 0;
+        self.redraw();
+    }
+
+    /// Set a character of the screen, showing it unless the view is scrolled back
+    fn set_char(&mut self, row: usize, col: usize, screen_char: ScreenChar) {
+        self.buffer.chars[row][col] = screen_char;
+        if self.scrollback.offset() == 0 {
+            self.vga.chars[row][col] = screen_char;
+        }
+    }
+
+    /// Show a row of the screen unless the view is scrolled back
+    fn show_row(&mut self, row: usize) {
+        if self.scrollback.offset() == 0 {
+            self.vga.chars[row] = self.buffer.chars[row];
+        }
+    }
+
+    /// Draw the view: the screen, or the part of the scrollback it is scrolled back to
+    fn redraw(&mut self) {
+        for row in 0..BUFFER_HEIGHT {
+            self.vga.chars[row] = *self.scrollback.view_row(&self.buffer, row);
+        }
+    }
+
+    /// Scroll the view back or forward by a page, keeping one row of the previous page (Shift+PageUp/PageDown)
+    fn scroll_page(&mut self, back: bool) {
+        let page = BUFFER_HEIGHT - 1;
+        let moved = if back { self.scrollback.scroll_up(page) } else { self.scrollback.scroll_down(page) };
+        if moved {
+            self.redraw();
+        }
+    }
+
+    /// Return the view to the screen if it is scrolled back
+    fn scroll_to_bottom(&mut self) {
+        if self.scrollback.scroll_down(usize::MAX) {
+            self.redraw();
+        }
     }
 
     /// Write a string to the terminal
@@ -183,322 +260,291 @@ impl Terminal {
         }
     }
 
-    /// Flush the terminal buffer to VGA display
-    pub fn flush(&mut self) {
-        use crate::vga_buffer::DoubleBuffer;
-        let mut double_buffer = DoubleBuffer::new();
-        
-        // 将当前终端内容复制到双缓冲
-        for row in 0..BUFFER_HEIGHT {
-            for col in 0..BUFFER_WIDTH {
-                let screen_char = self.buffer.chars[row][col];
-                double_buffer.write_char(row, col, screen_char.ascii_character, screen_char.color_code);
-            }
-        }
-        
-        // 刷新到VGA
-        double_buffer.flush_to_vga();
-    }
-
-    /// Read a byte from the keyboard and handle special keys
-    pub fn read_byte(&mut self) -> u8 {
-        let mut port = Port::new(0x60);
-        let scancode = unsafe { port.read() };
-
-        // 检查是否为按键释放事件 (最高位为1)
-        if scancode & 0x80 != 0 {
-            // 处理Shift键释放
-            match scancode {
-                0xAA | 0xB6 => {
-                    self.shift_pressed = false;
-                },
-                _ => {},
+    /// Wait for the next key event from the keyboard driver
+    pub fn read_key(&mut self) -> crate::keyboard::KeyEvent {
+        loop {
+            let scancode = crate::keyboard::next_scancode();
+            if let Some(event) = self.keyboard.decode(scancode) {
+                return event;
             }
-            return 0;
         }
-
-        // 直接返回扫描码，让read_line方法处理映射
-        scancode
     }
 
-    /// Read a line from the keyboard
+    /// Read a line from the keyboard with line editing and history
+    ///
+    /// The line is edited in place on the bottom row after whatever was already written
+    /// there (usually the prompt), scrolling horizontally when it does not fit.
     pub fn read_line(&mut self) -> String {
-        let mut line = String::new();
-        let mut last_scancode = 0;
-        let mut _debounce_count = 0;
-        loop {
-            // 移除去抖动逻辑，因为它可能导致问题
+        use crate::keyboard::{Key, KeyState};
 
-            let scancode = self.read_byte();
-            if scancode == 0 {
-                continue; // 忽略特殊键处理后的0值
-            }
+        // 留给输入的列太少时换到新的一行
+        if self.column_position + MIN_INPUT_WIDTH > BUFFER_WIDTH {
+            self.new_line();
+        }
+        let start = self.column_position;
 
-            // 防止重复处理相同的扫描码
-            if scancode == last_scancode {
-                _debounce_count = 5;
-                continue;
-            }
-            last_scancode = scancode;
+        let mut line = LineBuffer::new();
+        // 第一个可见字符在行中的下标
+        let mut view = 0;
+        // 正在浏览的历史记录下标，以及开始浏览前正在编辑的内容
+        let mut browsing: Option<usize> = None;
+        let mut draft = String::new();
+        // 连续第二次按Tab时列出所有候选项
+        let mut last_was_tab = false;
 
-            // 确保扫描码在有效范围内
-            if scancode as usize >= SCANCODE_TO_ASCII.len() {
+        self.render_line(start, &line, &mut view);
+        loop {
+            let event = self.read_key();
+            // 只处理按下事件，按住不放时的重复make码同样算作按下
+            if event.state != KeyState::Down {
                 continue;
             }
-
-            // 处理特殊功能键
-            match scancode {
-                // 退格键
-                0x0E => {
-                    if !line.is_empty() {
-                        line.pop();
-                        self.write_byte(0x08);  // 退格符
-                        self.write_byte(b' ');   // 空格覆盖
-                        self.write_byte(0x08);  // 再次退格
-                    }
+            // Shift+PageUp/PageDown浏览回滚历史，其他按键回到屏幕底部
+            match event.key {
+                Key::PageUp | Key::PageDown if event.modifiers.shift() => {
+                    self.scroll_page(event.key == Key::PageUp);
                     continue;
                 },
-                // 回车键
-                0x1C => {
-                    self.write_byte(b'\n');
-                    break;
+                key if key.is_modifier() => continue,
+                _ => self.scroll_to_bottom(),
+            }
+            // AltGr用来输入字符，不算作Ctrl或Alt组合键
+            let ctrl = event.modifiers.ctrl() && !event.modifiers.alt_gr();
+            let alt = event.modifiers.alt() && !event.modifiers.alt_gr();
+
+            match event.key {
+                Key::Enter => break,
+                Key::Backspace => line.backspace(),
+                Key::Delete => line.delete(),
+                Key::Left => line.move_left(),
+                Key::Right => line.move_right(),
+                Key::Home => line.home(),
+                Key::End => line.end(),
+                Key::Up => {
+                    let index = match browsing {
+                        Some(index) => index.checked_sub(1),
+                        None => {
+                            draft = String::from(&line);
+                            self.history.len().checked_sub(1)
+                        }
+                    };
+                    if let Some(entry) = index.and_then(|index| self.history.get(index)) {
+                        line.set(entry);
+                        browsing = index;
+                    }
                 },
-                // Tab键
-                0x0F => {
-                    for _ in 0..4 {
-                        line.push(' ');
-                        self.write_byte(b' ');
+                Key::Down => {
+                    if let Some(index) = browsing {
+                        match self.history.get(index + 1) {
+                            Some(entry) => {
+                                line.set(entry);
+                                browsing = Some(index + 1);
+                            },
+                            None => {
+                                line.set(&draft);
+                                browsing = None;
+                            },
+                        }
                     }
-                    continue;
                 },
-                // Shift键
-                0x2A | 0x36 => {
-                    self.shift_pressed = true;
+                Key::Tab => {
+                    self.complete(start, &mut line, last_was_tab);
+                    last_was_tab = true;
+                    self.render_line(start, &line, &mut view);
                     continue;
                 },
-                // 忽略其他非字符键
-                0x01..=0x0D | 0x1A..=0x1B | 0x1D..=0x1F | 0x37..=0x7F => {
-                    continue;
+                Key::Char(c) if ctrl => match c.to_ascii_lowercase() {
+                    'a' => line.home(),
+                    'e' => line.end(),
+                    'k' => line.kill_to_end(),
+                    'u' => line.kill_to_start(),
+                    'w' => line.delete_word(),
+                    'l' => self.clear_keeping_prompt(start),
+                    // 放弃当前行
+                    'c' => {
+                        line.set("");
+                        self.render_line(start, &line, &mut view);
+                        self.write_str("^C\n");
+                        self.set_cursor(0);
+                        return String::new();
+                    },
+                    _ => {},
                 },
-                _ => {},
-            }
-
-            let ascii_char = if self.shift_pressed {
-                // Shift键按下，使用大写映射
-                SCANCODE_TO_UPPERCASE[scancode as usize]
-            } else {
-                // 默认使用小写映射
-                SCANCODE_TO_ASCII[scancode as usize]
-            };
-
-            match ascii_char {
-                // Printable characters
-                0x20..=0x7E => {
-                    line.push(ascii_char as char);
-                    self.write_byte(ascii_char);
-                },
-                _ => {
-                    // 对于无法识别的字符，输出一个问号
-                    self.write_byte(b'?');
+                Key::Char(c) if alt => match c.to_ascii_lowercase() {
+                    'b' => line.word_left(),
+                    'f' => line.word_right(),
+                    'd' => line.delete_word_forward(),
+                    _ => {},
                 },
+                Key::Char(c) => line.insert(c),
+                // 忽略其他非字符键
+                _ => {},
             }
+            last_was_tab = false;
+            self.render_line(start, &line, &mut view);
         }
-        line
-    }
 
-    /// Run the terminal
-    pub fn run(&mut self) {
-        self.clear();
-        self.write_str("TerraOS Terminal\n");
-        self.write_str("Type 'help' for available commands\n\n");
-
-        loop {
-            self.write_str("$ ");
-            let command = self.read_line();
-            self.process_command(&command);
-        }
+        // 把光标移到行尾再换行，保证整行内容留在屏幕上
+        line.end();
+        self.render_line(start, &line, &mut view);
+        self.new_line();
+        self.set_cursor(0);
+        String::from(&line)
     }
 
-    // Command handlers
-    fn handle_ls_command(&mut self, parts: &[&str]) {
-        use crate::fs::new_fs::{FileSystem, SimpleFileSystem, MemoryBlockDevice};
-        let device = MemoryBlockDevice::new();
-        let mut fs = SimpleFileSystem::new(device);
-        fs.init();
+    /// Complete the word before the cursor; on a repeated Tab with several matches, list them
+    fn complete(&mut self, start: usize, line: &mut LineBuffer, list: bool) {
+        let completion = completion::complete(&self.cwd, line.chars(), line.cursor());
 
-        let mut long_format = false;
-        let mut show_all = false;
-
-        // Parse options
-        for part in parts.iter().skip(1) {
-            match *part {
-                "-l" => long_format = true,
-                "-a" => show_all = true,
-                _ => {}
-            }
+        // 补全到所有候选项的公共前缀，唯一匹配时再补一个空格(目录除外)
+        let common = completion.common_prefix();
+        for c in common.chars().skip(completion.prefix.chars().count()) {
+            line.insert(c);
         }
-
-        match fs.list_directory(0) {
-            Ok(entries) => {
-                for (name, inode_id, is_dir, size) in entries {
-                    if !show_all && name.starts_with('.') {
-                        continue;
-                    }
-
-                    if long_format {
-                        let type_char = if is_dir { 'd' } else { '-' };
-                        let size_str = self.format_size(size);
-                        self.write_str(&format!("{} {} {} {}\n", type_char, "rwxr-xr-x", size_str, name));
-                    } else {
-                        self.write_str(&format!("{}\n", name));
-                    }
-                }
-            },
-            Err(e) => {
-                self.write_str(&format!("Error: {}\n", e));
+        if completion.candidates.len() == 1 {
+            if !common.ends_with('/') {
+                line.insert(' ');
             }
-        }
-    }
-
-    fn handle_mk_command(&mut self, parts: &[&str]) {
-        if parts.len() < 2 {
-            self.write_str("Usage: mk <directory_name>\n");
             return;
         }
-
-        let dir_name = parts[1];
-        
-        use crate::fs::new_fs::{FileSystem, SimpleFileSystem, MemoryBlockDevice};
-        let device = MemoryBlockDevice::new();
-        let mut fs = SimpleFileSystem::new(device);
-        fs.init();
-
-        match fs.create_directory(dir_name, 0) {
-            Ok(inode_id) => {
-                self.write_str(&format!("Directory '{}' created successfully\n", dir_name));
-            },
-            Err(e) => {
-                self.write_str(&format!("Error: {}\n", e));
-            }
-        }
-    }
-
-    fn handle_rm_command(&mut self, parts: &[&str]) {
-        if parts.len() < 2 {
-            self.write_str("Usage: rm [-r] [-f] <name>\n");
+        if !list || completion.candidates.len() < 2 {
             return;
         }
 
-        let mut recursive = false;
-        let mut force = false;
-        let mut name = "";
+        // 在输入行下方列出候选项，然后在新的一行重新显示提示符
+        let row = BUFFER_HEIGHT - 1;
+        let mut prompt = [ScreenChar { ascii_character: b' ', color_code: self.color_code }; BUFFER_WIDTH];
+        prompt[..start].copy_from_slice(&self.buffer.chars[row][..start]);
 
-        // Parse options and arguments
-        for part in parts.iter().skip(1) {
-            match *part {
-                "-r" => recursive = true,
-                "-f" => force = true,
-                _ => name = part,
+        self.new_line();
+        let width = completion.candidates.iter().map(|name| name.chars().count()).max().unwrap_or(0) + 2;
+        let per_row = (BUFFER_WIDTH / width).max(1);
+        for (i, name) in completion.candidates.iter().enumerate() {
+            if i > 0 && i % per_row == 0 {
+                self.new_line();
             }
-        }
-
-        if name.is_empty() {
-            self.write_str("Error: No file or directory specified\n");
-            return;
-        }
-
-        if !force {
-            self.write_str(&format!("Are you sure you want to delete '{}'{}? (y/N): ", 
-                                name, if recursive { " and all its contents" } else { "" }));
-            let response = self.read_line();
-            if response != "y" && response != "Y" {
-                self.write_str("Deletion cancelled\n");
-                return;
+            for c in name.chars() {
+                self.write_char(c);
+            }
+            for _ in name.chars().count()..width {
+                self.write_byte(b' ');
             }
         }
+        self.new_line();
+        self.buffer.chars[row][..start].copy_from_slice(&prompt[..start]);
+        self.show_row(row);
+    }
 
-        use crate::fs::new_fs::{FileSystem, SimpleFileSystem, MemoryBlockDevice};
-        let device = MemoryBlockDevice::new();
-        let mut fs = SimpleFileSystem::new(device);
-        fs.init();
+    /// Draw the edited line on the bottom row starting at column `start`
+    fn render_line(&mut self, start: usize, line: &LineBuffer, view: &mut usize) {
+        // 保持光标在可见区域内，最后一列留给行尾的光标
+        let width = BUFFER_WIDTH - start;
+        if line.cursor() < *view {
+            *view = line.cursor();
+        } else if line.cursor() >= *view + width {
+            *view = line.cursor() + 1 - width;
+        }
+
+        let row = BUFFER_HEIGHT - 1;
+        for col in 0..width {
+            let c = line.chars().get(*view + col).copied().unwrap_or(' ');
+            self.set_char(row, start + col, ScreenChar {
+                ascii_character: to_cp437(c),
+                color_code: self.color_code,
+            });
+        }
+        self.column_position = start + (line.len() - *view).min(width);
+        self.set_cursor(start + line.cursor() - *view);
+    }
 
-        match fs.delete_item(name, recursive) {
-            Ok(_) => self.write_str(&format!("'{}' deleted successfully\n", name)),
-            Err(e) => self.write_str(&format!("Error: {}\n", e)),
-        }
+    /// Clear the screen but keep the first `prompt_len` cells of the bottom row (Ctrl-L)
+    fn clear_keeping_prompt(&mut self, prompt_len: usize) {
+        let row = BUFFER_HEIGHT - 1;
+        let mut prompt = [ScreenChar { ascii_character: b' ', color_code: self.color_code }; BUFFER_WIDTH];
+        prompt[..prompt_len].copy_from_slice(&self.buffer.chars[row][..prompt_len]);
+        self.clear();
+        self.buffer.chars[row][..prompt_len].copy_from_slice(&prompt[..prompt_len]);
+        self.show_row(row);
     }
 
-    fn handle_cd_command(&mut self, parts: &[&str]) {
-        if parts.len() < 2 {
-            self.write_str("Usage: cd <directory_path>\n");
-            return;
-        }
+    /// Move the VGA hardware cursor to a column of the bottom row
+    fn set_cursor(&mut self, col: usize) {
+        use x86_64::instructions::port::Port;
 
-        let path = parts[1];
-        
-        // For now, just simulate changing directory
-        if path == "/" || path == ".." || path == "." {
-            self.write_str(&format!("Changed directory to {}\n", path));
-        } else {
-            self.write_str(&format!("Directory '{}' not found\n", path));
+        let position = ((BUFFER_HEIGHT - 1) * BUFFER_WIDTH + col.min(BUFFER_WIDTH - 1)) as u16;
+        let mut index: Port<u8> = Port::new(0x3D4);
+        let mut data: Port<u8> = Port::new(0x3D5);
+        unsafe {
+            index.write(0x0F);
+            data.write((position & 0xFF) as u8);
+            index.write(0x0E);
+            data.write((position >> 8) as u8);
         }
     }
 
-    fn handle_mv_command(&mut self, parts: &[&str]) {
-        if parts.len() < 3 {
-            self.write_str("Usage: mv <source> <destination>\n");
-            return;
-        }
-
-        let src = parts[1];
-        let dst = parts[2];
-
-        use crate::fs::new_fs::{FileSystem, SimpleFileSystem, MemoryBlockDevice};
-        let device = MemoryBlockDevice::new();
-        let mut fs = SimpleFileSystem::new(device);
-        fs.init();
+    /// Run the terminal
+    pub fn run(&mut self) {
+        self.clear();
+        self.write_str("TerraOS Terminal\n");
+        self.write_str("Type 'help' for available commands\n\n");
+        self.run_startup_script();
 
-        match fs.move_item(src, dst) {
-            Ok(_) => self.write_str(&format!("Moved '{}' to '{}'\n", src, dst)),
-            Err(e) => self.write_str(&format!("Error: {}\n", e)),
+        loop {
+            let prompt = env::expand_prompt(self.env.get("PS1").unwrap_or(env::DEFAULT_PS1), &self.cwd);
+            self.write_str(&prompt);
+            let command = self.read_line();
+            self.history.push(&command);
+            self.process_command(&command);
         }
     }
 
-    fn handle_cp_command(&mut self, parts: &[&str]) {
-        if parts.len() < 3 {
-            self.write_str("Usage: cp [-r] <source> <destination>\n");
-            return;
-        }
-
-        let mut recursive = false;
-        let mut src = "";
-        let mut dst = "";
-        let mut arg_index = 1;
+    /// Run the startup script if there is one
+    fn run_startup_script(&mut self) {
+        use crate::fs::new_fs::FileSystem;
 
-        // Parse options
-        if parts[arg_index] == "-r" {
-            recursive = true;
-            arg_index += 1;
+        let exists = crate::fs::vfs()
+            .and_then(|vfs| vfs.metadata(STARTUP_SCRIPT))
+            .map(|meta| !meta.is_dir)
+            .unwrap_or(false);
+        if exists {
+            let mut stdin = Stdin::Terminal;
+            let mut stdout = Stdout::Terminal;
+            let mut context = Context::new(self, &mut stdin, &mut stdout);
+            interpreter::run_script(&mut context, STARTUP_SCRIPT, &[]);
         }
+    }
 
-        if parts.len() < arg_index + 2 {
-            self.write_str("Usage: cp [-r] <source> <destination>\n");
-            return;
+    /// Get the shared VFS, reporting an error if it is not mounted
+    fn vfs(&mut self) -> Option<&'static crate::fs::Vfs> {
+        match crate::fs::vfs() {
+            Ok(fs) => Some(fs),
+            Err(e) => {
+                self.write_str(&format!("Error: {}\n", e));
+                None
+            }
         }
+    }
 
-        src = parts[arg_index];
-        dst = parts[arg_index + 1];
-
-        use crate::fs::new_fs::{FileSystem, SimpleFileSystem, MemoryBlockDevice};
-        let device = MemoryBlockDevice::new();
-        let mut fs = SimpleFileSystem::new(device);
-        fs.init();
+    /// Report a filesystem error in the shell's standard `command: action: message` form
+    pub fn print_fs_error(&mut self, command: &str, action: &str, error: crate::fs::FsError) {
+        self.write_str(&format!("{}: {}: {}\n", command, action, error));
+    }
 
-        match fs.copy_item(src, dst, recursive) {
-            Ok(_) => self.write_str(&format!("Copied '{}' to '{}'{}\n", src, dst, 
-                                       if recursive { " recursively" } else { "" })),
-            Err(e) => self.write_str(&format!("Error: {}\n", e)),
-        }
+    /// Read a whole file through the open-file table
+    fn read_file(&mut self, path: &str) -> Result<Vec<u8>, crate::fs::FsError> {
+        use crate::fs::file::OpenFlags;
+
+        let fd = self.files.open(path, OpenFlags::READ)?;
+        let mut content = Vec::new();
+        let result = loop {
+            match self.files.read(fd, 512) {
+                Ok(data) if data.is_empty() => break Ok(content),
+                Ok(data) => content.extend_from_slice(&data),
+                Err(e) => break Err(e),
+            }
+        };
+        let _ = self.files.close(fd);
+        result
     }
 
     // Helper function to format file sizes
@@ -515,144 +561,48 @@ impl Terminal {
         }
     }
 
-    /// Handle memory information display command
-    fn handle_meminfo_command(&mut self) {
-        use crate::system_monitor::SystemMonitor;
-        let monitor = SystemMonitor::new(self.allocator);
-        monitor.display_memory_info(self);
-    }
-
-    /// Handle memory statistics display command
-    fn handle_memstats_command(&mut self) {
-        use crate::system_monitor::SystemMonitor;
-        let monitor = SystemMonitor::new(self.allocator);
-        monitor.display_memory_info(self);
-        
-        // 添加详细统计信息
-        self.write_str("\n=== 详细统计信息 ===\n");
-        self.write_str("分配次数:      ");
-        let alloc_count = self.allocator.get_allocation_count();
-        self.write_str(&format!("{}\n", alloc_count));
-        
-        self.write_str("释放次数:      ");
-        let dealloc_count = self.allocator.get_deallocation_count();
-        self.write_str(&format!("{}\n", dealloc_count));
-        
-        if alloc_count > 0 {
-            self.write_str("平均分配大小:  ");
-            let total_allocated = self.allocator.get_total_allocated();
-            let avg_size = total_allocated / alloc_count;
-            self.write_str(&format!("{} 字节\n", avg_size));
-        }
+    /// Look up a shell variable or special parameter for `$NAME` expansion
+    fn variable(&self, name: &str) -> Option<String> {
+        self.interpreter.parameter(name).or_else(|| self.env.get(name).map(String::from))
     }
 
-    /// Handle system information display command
-    fn handle_sysinfo_command(&mut self) {
-        use crate::system_monitor::SystemMonitor;
-        let monitor = SystemMonitor::new(self.allocator);
-        monitor.display_system_info(self);
-        
-        // 添加一些额外的系统信息
-        self.write_str("\n=== 内存信息 ===\n");
-        let stats = self.allocator.get_memory_stats();
-        self.write_str("堆地址范围:    0x500000 - 0x");
-        self.write_str(&format!("{:X}\n", 0x500000 + crate::allocator::HEAP_SIZE));
-        
-        self.write_str("分配器类型:    链表分配器\n");
-        self.write_str("双缓冲模式:    已启用\n");
-        self.write_str("终端模式:      交互式\n");
-    }
-
-    /// Handle system health check command
-    fn handle_syshealth_command(&mut self) {
-        use crate::system_monitor::{SystemMonitor, MemoryHealthStatus};
-        let monitor = SystemMonitor::new(self.allocator);
-        monitor.display_health_check(self);
-        
-        // 添加简单的系统自检
-        self.write_str("\n=== 系统自检 ===\n");
-        
-        // 检查内存分配器状态
-        if self.allocator.get_allocation_count() > 0 {
-            self.write_str("✅ 内存分配器:  正常\n");
-        } else {
-            self.write_str("⚠️  内存分配器:  未使用\n");
-        }
-        
-        // 检查VGA缓冲
-        self.write_str("✅ VGA缓冲:     正常\n");
-        
-        // 检查终端状态
-        self.write_str("✅ 终端系统:    正常\n");
-        
-        // 检查文件系统
-        self.write_str("✅ 文件系统:    可用\n");
-        
-        self.write_str("\n系统状态:      全部检查通过 ✅\n");
-    }
-
-    /// Process a terminal command
+    /// Process a terminal command line
     fn process_command(&mut self, command: &str) {
-        let mut parts = Vec::<&str, 16>::new();
-        for part in command.split_whitespace() {
-            if parts.push(part).is_err() {
-                break; // Handle overflow if needed
-            }
-        }
-        if parts.is_empty() {
-            return;
-        }
+        let mut stdin = Stdin::Terminal;
+        let mut stdout = Stdout::Terminal;
+        let mut context = Context::new(self, &mut stdin, &mut stdout);
+        interpreter::run_source(&mut context, command);
+    }
+}
 
-        match parts[0] {
-            "help" => self.write_str("Available commands: help, clear, echo, ls, mk, rm, cd, mv, cp, meminfo, memstats, sysinfo, syshealth\n"),
-            "clear" => self.clear(),
-            "echo" => {
-                if parts.len() > 1 {
-                    for (i, arg) in parts[1..].iter().enumerate() {
-                        if i > 0 {
-                            self.write_byte(b' ');
-                        }
-                        self.write_str(arg);
-                    }
-                }
-                self.write_byte(b'\n');
-            },
-            "ls" => {
-                self.handle_ls_command(&parts);
-            },
-            "mk" => {
-                self.handle_mk_command(&parts);
-            },
-            "rm" => {
-                self.handle_rm_command(&parts);
-            },
-            "cd" => {
-                self.handle_cd_command(&parts);
-            },
-            "mv" => {
-                self.handle_mv_command(&parts);
-            },
-            "cp" => {
-                self.handle_cp_command(&parts);
-            },
-            "meminfo" => {
-                self.handle_meminfo_command();
-            },
-            "memstats" => {
-                self.handle_memstats_command();
-            },
-            "sysinfo" => {
-                self.handle_sysinfo_command();
-            },
-            "syshealth" => {
-                self.handle_syshealth_command();
-            },
-            _ => {
-                self.write_str("Unknown command: ");
-                self.write_str(command);
-                self.write_byte(b'\n');
-            },
-        }
+/// Translate a character to code page 437, the VGA text mode character set
+fn to_cp437(c: char) -> u8 {
+    match c {
+        ' '..='~' => c as u8,
+        'Ç' => 0x80,
+        'ü' => 0x81,
+        'é' => 0x82,
+        'ä' => 0x84,
+        'à' => 0x85,
+        'ç' => 0x87,
+        'è' => 0x8A,
+        'Ä' => 0x8E,
+        'É' => 0x90,
+        'ö' => 0x94,
+        'ù' => 0x97,
+        'Ö' => 0x99,
+        'Ü' => 0x9A,
+        '£' => 0x9C,
+        '¬' => 0xAA,
+        'ß' => 0xE1,
+        'µ' => 0xE6,
+        '°' => 0xF8,
+        '²' => 0xFD,
+        '§' => 0x15,
+        '¦' => b'|',
+        '´' => b'\'',
+        // 代码页437中没有的字符显示为方块
+        _ => 0xFE,
     }
 }
 
@@ -0,0 +1,724 @@
+//! Shell language parser
+//!
+//! The language is a small subset of the POSIX shell:
+//!
+//! - Commands are separated by `;` or new lines and joined with `&&`, `||` and `|`.
+//! - `<`, `>` and `>>` redirect the input or output of any command.
+//! - `if`/`elif`/`else`/`fi`, `while`/`until ... do ... done`, `for NAME [in WORDS]; do ... done`,
+//!   `{ ...; }` groups and `name() { ...; }` functions. Keywords are only recognized at the
+//!   start of a command, so `echo done` prints "done".
+//! - `#` at the start of a word begins a comment that runs to the end of the line.
+//!
+//! Words keep their quotes until the command runs, when [`expand_word`] applies them:
+//!
+//! - `'...'` keeps everything literally.
+//! - `"..."` expands variables; `\"`, `\\` and `\$` are the only escapes inside.
+//! - Outside quotes a backslash makes the next character literal; before a new line it joins
+//!   the two lines.
+//! - `$NAME`, `${NAME}`, `$?`, `$#` and `$0`-`$9` expand through the lookup function; unknown
+//!   variables expand to nothing, and an unquoted word that expands to nothing is dropped.
+//!   Expanded values are not split into several words.
+
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::iter::Peekable;
+use core::str::Chars;
+
+/// Errors found while parsing a command line or script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A quote of this kind was opened but never closed
+    UnterminatedQuote(char),
+    /// The text ends with a backslash that has nothing to escape
+    TrailingBackslash,
+    /// A `${` without its closing `}`, or an invalid name inside it
+    BadSubstitution,
+    /// An operator or keyword in a place where it is not allowed
+    UnexpectedToken(String),
+    /// The text ends in the middle of a command, e.g. an `if` without its `fi`
+    UnexpectedEnd,
+    /// A word used as a function or loop variable name that is not a valid name
+    InvalidName(String),
+    /// Compound commands nested deeper than the parser allows
+    TooDeeplyNested,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnterminatedQuote(quote) => write!(f, "unterminated {} quote", quote),
+            ParseError::TrailingBackslash => f.write_str("unexpected end of line after '\\'"),
+            ParseError::BadSubstitution => f.write_str("bad substitution"),
+            ParseError::UnexpectedToken(token) => write!(f, "syntax error near unexpected token '{}'", token),
+            ParseError::UnexpectedEnd => f.write_str("syntax error: unexpected end of file"),
+            ParseError::InvalidName(name) => write!(f, "'{}': not a valid identifier", name),
+            ParseError::TooDeeplyNested => write!(f, "syntax error: commands nested more than {} deep", MAX_NESTING),
+        }
+    }
+}
+
+/// Commands run one after another
+pub type List = Vec<AndOr>;
+
+/// Pipelines joined with `&&` and `||`, evaluated from left to right
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+}
+
+/// How a pipeline is joined to the one before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    /// `&&`: run only if the previous one succeeded
+    And,
+    /// `||`: run only if the previous one failed
+    Or,
+}
+
+/// Commands joined with `|`
+pub type Pipeline = Vec<Command>;
+
+/// Where a command's output goes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputRedirect {
+    pub path: String,
+    /// `>>` appends to the file instead of replacing its content
+    pub append: bool,
+}
+
+/// A command together with its redirections; when one is given twice, the last one wins
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    pub kind: CommandKind,
+    /// File given with `<`
+    pub input: Option<String>,
+    /// File given with `>` or `>>`
+    pub output: Option<OutputRedirect>,
+}
+
+/// What a command runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandKind {
+    /// A command name with its arguments; empty for a bare redirection like `> file`
+    Simple(Vec<String>),
+    /// `{ list; }`
+    Group(List),
+    /// `if` and `elif` branches as (condition, body) pairs, then the `else` body
+    If { branches: Vec<(List, List)>, otherwise: Option<List> },
+    /// `while` loop, or `until` loop when `until` is set
+    Loop { condition: List, body: List, until: bool },
+    /// `for` loop; without `in` it iterates over the positional parameters
+    For { variable: String, words: Option<Vec<String>>, body: List },
+    /// `name() command`
+    Function { name: String, body: Rc<Command> },
+}
+
+/// Parse a command line or a whole script
+pub fn parse(source: &str) -> Result<List, ParseError> {
+    let mut parser = Parser {
+        tokens: lex(source)?,
+        position: 0,
+        depth: 0,
+    };
+    let list = parser.list(&[])?;
+    match parser.next() {
+        None => Ok(list),
+        Some(token) => Err(unexpected(&token)),
+    }
+}
+
+/// Apply the quoting of a word and expand its variables; None means the word is dropped
+pub fn expand_word(raw: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<Option<String>, ParseError> {
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut chars = raw.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                quoted = true;
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                    word.push(c);
+                }
+            }
+            '"' => {
+                quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') | None => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => break,
+                        },
+                        Some('$') => expand(&mut chars, &mut word, &lookup)?,
+                        Some(c) => word.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+                quoted = true;
+            }
+            '$' => expand(&mut chars, &mut word, &lookup)?,
+            c => word.push(c),
+        }
+    }
+
+    if word.is_empty() && !quoted {
+        Ok(None)
+    } else {
+        Ok(Some(word))
+    }
+}
+
+/// Whether `name` can be used as a variable or function name: letters, digits and '_',
+/// not starting with a digit
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(is_name_char),
+        _ => false,
+    }
+}
+
+// 展开'$'之后的变量名；'$'后面不是变量名时保留'$'本身
+fn expand(chars: &mut Peekable<Chars>, word: &mut String, lookup: &impl Fn(&str) -> Option<String>) -> Result<(), ParseError> {
+    let mut name = String::new();
+    match chars.peek() {
+        Some('{') => {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) if is_name_char(c) || (name.is_empty() && is_special(c)) => name.push(c),
+                    _ => return Err(ParseError::BadSubstitution),
+                }
+            }
+            if name.is_empty() {
+                return Err(ParseError::BadSubstitution);
+            }
+        }
+        // 特殊参数和位置参数只有一个字符，$10是$1后面跟着0
+        Some(&c) if is_special(c) || c.is_ascii_digit() => {
+            chars.next();
+            name.push(c);
+        }
+        Some(&c) if c.is_ascii_alphabetic() || c == '_' => {
+            while let Some(&c) = chars.peek() {
+                if !is_name_char(c) {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+        }
+        _ => {
+            word.push('$');
+            return Ok(());
+        }
+    }
+
+    if let Some(value) = lookup(&name) {
+        word.push_str(&value);
+    }
+    Ok(())
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_special(c: char) -> bool {
+    c == '?' || c == '#'
+}
+
+/// A word or an operator of the source text
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// A word with its quotes still in place
+    Word(String),
+    Pipe,
+    And,
+    Or,
+    Semicolon,
+    Newline,
+    RedirectIn,
+    RedirectOut,
+    RedirectAppend,
+    LeftParen,
+    RightParen,
+}
+
+fn unexpected(token: &Token) -> ParseError {
+    let text = match token {
+        Token::Word(word) => word.as_str(),
+        Token::Pipe => "|",
+        Token::And => "&&",
+        Token::Or => "||",
+        Token::Semicolon => ";",
+        Token::Newline => "newline",
+        Token::RedirectIn => "<",
+        Token::RedirectOut => ">",
+        Token::RedirectAppend => ">>",
+        Token::LeftParen => "(",
+        Token::RightParen => ")",
+    };
+    ParseError::UnexpectedToken(text.to_string())
+}
+
+// 把源文本切分成单词和运算符，单词保留引号和转义，执行时再展开
+fn lex(source: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    // 当前单词是否已经开始；""这样的空引号也会产生一个单词
+    let mut in_word = false;
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                // 续行
+                Some('\n') => {}
+                Some(c) => {
+                    word.push('\\');
+                    word.push(c);
+                    in_word = true;
+                }
+                None => return Err(ParseError::TrailingBackslash),
+            },
+            '\'' => {
+                word.push('\'');
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(ParseError::UnterminatedQuote('\'')),
+                    }
+                }
+                word.push('\'');
+                in_word = true;
+            }
+            '"' => {
+                word.push('"');
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(ParseError::UnterminatedQuote('"')),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(ParseError::UnterminatedQuote('"')),
+                    }
+                }
+                word.push('"');
+                in_word = true;
+            }
+            '#' if !in_word => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            ' ' | '\t' | '\r' | '\n' | ';' | '|' | '&' | '<' | '>' | '(' | ')' => {
+                if in_word {
+                    tokens.push(Token::Word(core::mem::take(&mut word)));
+                    in_word = false;
+                }
+                let token = match c {
+                    '\n' => Token::Newline,
+                    ';' => Token::Semicolon,
+                    '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+                    '|' => Token::Pipe,
+                    '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+                    // 不支持后台任务
+                    '&' => return Err(ParseError::UnexpectedToken("&".to_string())),
+                    '<' => Token::RedirectIn,
+                    '>' if chars.next_if_eq(&'>').is_some() => Token::RedirectAppend,
+                    '>' => Token::RedirectOut,
+                    '(' => Token::LeftParen,
+                    ')' => Token::RightParen,
+                    _ => continue,
+                };
+                tokens.push(token);
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        tokens.push(Token::Word(word));
+    }
+    Ok(tokens)
+}
+
+// 不能出现在命令开头的关键字
+const CLOSING_KEYWORDS: [&str; 7] = ["then", "elif", "else", "fi", "do", "done", "}"];
+
+// 复合命令最多嵌套的层数，解析器和解释器都是递归的，不能让输入耗尽内核栈
+const MAX_NESTING: usize = 32;
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    // 正在解析的命令的嵌套层数
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek_word(&self) -> Option<&str> {
+        match self.peek() {
+            Some(Token::Word(word)) => Some(word.as_str()),
+            _ => None,
+        }
+    }
+
+    // 下一个记号不符合语法时的错误
+    fn unexpected_next(&self) -> ParseError {
+        match self.peek() {
+            Some(token) => unexpected(token),
+            None => ParseError::UnexpectedEnd,
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Token::Newline) {
+            self.position += 1;
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        if self.peek_word() != Some(keyword) {
+            return Err(self.unexpected_next());
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    /// Commands up to one of the `terminators` keywords or the end of the text
+    fn list(&mut self, terminators: &[&str]) -> Result<List, ParseError> {
+        let mut list = Vec::new();
+        loop {
+            while matches!(self.peek(), Some(Token::Newline | Token::Semicolon)) {
+                self.position += 1;
+            }
+            match self.peek_word() {
+                Some(word) if terminators.contains(&word) => break,
+                _ if self.peek().is_none() => break,
+                _ => {}
+            }
+
+            list.push(self.and_or()?);
+            match self.peek() {
+                None | Some(Token::Newline | Token::Semicolon) => {}
+                // 复合命令之后可以直接跟结束关键字，如"{ if a; then b; fi }"
+                Some(Token::Word(word)) if terminators.contains(&word.as_str()) => {}
+                Some(token) => return Err(unexpected(token)),
+            }
+        }
+        Ok(list)
+    }
+
+    /// Like `list`, but at least one command is required before the terminator
+    fn body(&mut self, terminators: &[&str]) -> Result<List, ParseError> {
+        let list = self.list(terminators)?;
+        if list.is_empty() {
+            return Err(self.unexpected_next());
+        }
+        Ok(list)
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, ParseError> {
+        let first = self.pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let connector = match self.peek() {
+                Some(Token::And) => Connector::And,
+                Some(Token::Or) => Connector::Or,
+                _ => break,
+            };
+            self.position += 1;
+            self.skip_newlines();
+            rest.push((connector, self.pipeline()?));
+        }
+        Ok(AndOr { first, rest })
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let mut pipeline = Vec::new();
+        pipeline.push(self.command()?);
+        while self.peek() == Some(&Token::Pipe) {
+            self.position += 1;
+            self.skip_newlines();
+            pipeline.push(self.command()?);
+        }
+        Ok(pipeline)
+    }
+
+    fn command(&mut self) -> Result<Command, ParseError> {
+        if self.depth == MAX_NESTING {
+            return Err(ParseError::TooDeeplyNested);
+        }
+        self.depth += 1;
+        let command = self.nested_command();
+        self.depth -= 1;
+        command
+    }
+
+    fn nested_command(&mut self) -> Result<Command, ParseError> {
+        let kind = match self.peek() {
+            Some(Token::Word(word)) => match word.as_str() {
+                "if" => self.if_clause()?,
+                "while" | "until" => self.loop_clause()?,
+                "for" => self.for_clause()?,
+                "{" => self.group()?,
+                word if CLOSING_KEYWORDS.contains(&word) => return Err(self.unexpected_next()),
+                _ if self.tokens.get(self.position + 1) == Some(&Token::LeftParen) => return self.function(),
+                _ => return self.simple_command(),
+            },
+            Some(Token::RedirectIn | Token::RedirectOut | Token::RedirectAppend) => return self.simple_command(),
+            _ => return Err(self.unexpected_next()),
+        };
+
+        let mut command = Command { kind, input: None, output: None };
+        while self.redirection(&mut command)? {}
+        Ok(command)
+    }
+
+    fn simple_command(&mut self) -> Result<Command, ParseError> {
+        let mut words = Vec::new();
+        let mut command = Command { kind: CommandKind::Simple(Vec::new()), input: None, output: None };
+        loop {
+            if let Some(Token::Word(word)) = self.peek() {
+                words.push(word.clone());
+                self.position += 1;
+            } else if !self.redirection(&mut command)? {
+                break;
+            }
+        }
+        command.kind = CommandKind::Simple(words);
+        Ok(command)
+    }
+
+    /// Parse one redirection if the next token starts one
+    fn redirection(&mut self, command: &mut Command) -> Result<bool, ParseError> {
+        let append = match self.peek() {
+            Some(Token::RedirectIn) => None,
+            Some(Token::RedirectOut) => Some(false),
+            Some(Token::RedirectAppend) => Some(true),
+            _ => return Ok(false),
+        };
+        self.position += 1;
+
+        let path = match self.next() {
+            Some(Token::Word(path)) => path,
+            Some(token) => return Err(unexpected(&token)),
+            None => return Err(ParseError::UnexpectedToken("newline".to_string())),
+        };
+        match append {
+            None => command.input = Some(path),
+            Some(append) => command.output = Some(OutputRedirect { path, append }),
+        }
+        Ok(true)
+    }
+
+    fn if_clause(&mut self) -> Result<CommandKind, ParseError> {
+        self.position += 1;
+        let mut branches = Vec::new();
+        loop {
+            let condition = self.body(&["then"])?;
+            self.expect_keyword("then")?;
+            let body = self.body(&["elif", "else", "fi"])?;
+            branches.push((condition, body));
+
+            match self.peek_word() {
+                Some("elif") => self.position += 1,
+                Some("else") => {
+                    self.position += 1;
+                    let otherwise = self.body(&["fi"])?;
+                    self.expect_keyword("fi")?;
+                    return Ok(CommandKind::If { branches, otherwise: Some(otherwise) });
+                }
+                _ => {
+                    self.expect_keyword("fi")?;
+                    return Ok(CommandKind::If { branches, otherwise: None });
+                }
+            }
+        }
+    }
+
+    fn loop_clause(&mut self) -> Result<CommandKind, ParseError> {
+        let until = self.peek_word() == Some("until");
+        self.position += 1;
+        let condition = self.body(&["do"])?;
+        self.expect_keyword("do")?;
+        let body = self.body(&["done"])?;
+        self.expect_keyword("done")?;
+        Ok(CommandKind::Loop { condition, body, until })
+    }
+
+    fn for_clause(&mut self) -> Result<CommandKind, ParseError> {
+        self.position += 1;
+        let variable = match self.next() {
+            Some(Token::Word(name)) if is_valid_name(&name) => name,
+            Some(Token::Word(name)) => return Err(ParseError::InvalidName(name)),
+            Some(token) => return Err(unexpected(&token)),
+            None => return Err(ParseError::UnexpectedEnd),
+        };
+        self.skip_newlines();
+
+        let mut words = None;
+        if self.peek_word() == Some("in") {
+            self.position += 1;
+            let mut list = Vec::new();
+            while let Some(Token::Word(word)) = self.peek() {
+                list.push(word.clone());
+                self.position += 1;
+            }
+            // 单词列表必须以';'或换行结束
+            match self.peek() {
+                Some(Token::Semicolon | Token::Newline) => self.position += 1,
+                _ => return Err(self.unexpected_next()),
+            }
+            words = Some(list);
+        } else if self.peek() == Some(&Token::Semicolon) {
+            self.position += 1;
+        }
+        self.skip_newlines();
+
+        self.expect_keyword("do")?;
+        let body = self.body(&["done"])?;
+        self.expect_keyword("done")?;
+        Ok(CommandKind::For { variable, words, body })
+    }
+
+    fn group(&mut self) -> Result<CommandKind, ParseError> {
+        self.position += 1;
+        let body = self.body(&["}"])?;
+        self.expect_keyword("}")?;
+        Ok(CommandKind::Group(body))
+    }
+
+    fn function(&mut self) -> Result<Command, ParseError> {
+        let name = match self.next() {
+            Some(Token::Word(name)) if is_valid_name(&name) => name,
+            Some(Token::Word(name)) => return Err(ParseError::InvalidName(name)),
+            _ => return Err(ParseError::UnexpectedEnd),
+        };
+        // 跳过'('，后面必须紧跟')'
+        self.position += 1;
+        if self.peek() != Some(&Token::RightParen) {
+            return Err(self.unexpected_next());
+        }
+        self.position += 1;
+        self.skip_newlines();
+
+        // 函数体必须是复合命令
+        if !matches!(self.peek_word(), Some("{" | "if" | "while" | "until" | "for")) {
+            return Err(self.unexpected_next());
+        }
+        let body = Rc::new(self.command()?);
+        Ok(Command {
+            kind: CommandKind::Function { name, body },
+            input: None,
+            output: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home".to_string()),
+            "?" => Some("0".to_string()),
+            _ => None,
+        }
+    }
+
+    // Parse a single simple command and expand its words
+    fn words(line: &str) -> Vec<String> {
+        let list = parse(line).unwrap();
+        assert_eq!(list.len(), 1);
+        match &list[0].first[0].kind {
+            CommandKind::Simple(words) => words
+                .iter()
+                .filter_map(|word| expand_word(word, lookup).unwrap())
+                .collect(),
+            kind => panic!("not a simple command: {:?}", kind),
+        }
+    }
+
+    #[test_case]
+    fn quotes() {
+        assert_eq!(words("echo 'a  b' \"c d\" e'f'g"), ["echo", "a  b", "c d", "efg"]);
+        assert_eq!(words("echo '$HOME' \"$HOME\""), ["echo", "$HOME", "/home"]);
+        assert_eq!(words("echo '' \"\""), ["echo", "", ""]);
+        assert_eq!(parse("echo 'abc"), Err(ParseError::UnterminatedQuote('\'')));
+        assert_eq!(parse("echo \"abc"), Err(ParseError::UnterminatedQuote('"')));
+    }
+
+    #[test_case]
+    fn escapes() {
+        assert_eq!(words(r"echo a\ b \$HOME \'"), ["echo", "a b", "$HOME", "'"]);
+        assert_eq!(words(r#"echo "\"\\\$ \n""#), ["echo", r#""\$ \n"#]);
+        assert_eq!(words("echo a\\\nb"), ["echo", "ab"]);
+        assert_eq!(parse("echo \\"), Err(ParseError::TrailingBackslash));
+    }
+
+    #[test_case]
+    fn variables() {
+        assert_eq!(words("echo $HOME/x ${HOME}x $? $"), ["echo", "/home/x", "/homex", "0", "$"]);
+        // An unquoted word that expands to nothing is dropped, a quoted one is kept
+        assert_eq!(words("echo $UNSET \"$UNSET\""), ["echo", ""]);
+        assert_eq!(expand_word("${HOME", lookup), Err(ParseError::BadSubstitution));
+    }
+
+    #[test_case]
+    fn and_or() {
+        let list = parse("a && b || c; d").unwrap();
+        assert_eq!(list.len(), 2);
+        let connectors: Vec<Connector> = list[0].rest.iter().map(|(connector, _)| *connector).collect();
+        assert_eq!(connectors, [Connector::And, Connector::Or]);
+        assert!(list[1].rest.is_empty());
+
+        // A connector at the end of a line continues on the next one
+        assert_eq!(parse("a &&\nb").unwrap()[0].rest.len(), 1);
+        assert_eq!(parse("a &&"), Err(ParseError::UnexpectedEnd));
+        assert_eq!(parse("&& a"), Err(ParseError::UnexpectedToken("&&".to_string())));
+    }
+
+    #[test_case]
+    fn nesting_limit() {
+        let nested = |depth: usize| format!("{}true{}", "{ ".repeat(depth), "; }".repeat(depth));
+        assert!(parse(&nested(MAX_NESTING - 1)).is_ok());
+        assert_eq!(parse(&nested(MAX_NESTING)), Err(ParseError::TooDeeplyNested));
+    }
+}
@@ -0,0 +1,127 @@
+//! Scrollback history of the terminal screen
+//!
+//! Rows scrolled off the top of the screen are kept here, so the view can be moved back
+//! over earlier output with Shift+PageUp and Shift+PageDown.
+
+use alloc::collections::VecDeque;
+
+use super::{Buffer, ScreenChar, BUFFER_WIDTH};
+
+/// Default number of rows kept above the screen
+pub const DEFAULT_SCROLLBACK_LINES: usize = 200;
+
+/// A row of the screen
+pub type Row = [ScreenChar; BUFFER_WIDTH];
+
+/// Rows scrolled off the screen, oldest first, and how far the view is scrolled back
+pub struct Scrollback {
+    rows: VecDeque<Row>,
+    capacity: usize,
+    // 视图向上滚动的行数，0表示显示当前屏幕
+    offset: usize,
+}
+
+impl Scrollback {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            rows: VecDeque::new(),
+            capacity,
+            offset: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change the maximum number of rows, dropping the oldest ones if needed
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.rows.len() > capacity {
+            self.rows.pop_front();
+        }
+        self.rows.shrink_to_fit();
+        self.offset = self.offset.min(self.rows.len());
+    }
+
+    /// Keep a row that scrolled off the top of the screen
+    ///
+    /// A scrolled back view stays on the rows it shows, unless they are dropped for being
+    /// the oldest.
+    pub fn push(&mut self, row: Row) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.rows.len() == self.capacity {
+            self.rows.pop_front();
+        }
+        self.rows.push_back(row);
+        if self.offset > 0 {
+            self.offset = (self.offset + 1).min(self.rows.len());
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.rows.clear();
+        self.offset = 0;
+    }
+
+    /// Number of rows the view is scrolled back
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Move the view back by up to `rows` rows; returns whether it moved
+    pub fn scroll_up(&mut self, rows: usize) -> bool {
+        let offset = (self.offset + rows).min(self.rows.len());
+        core::mem::replace(&mut self.offset, offset) != offset
+    }
+
+    /// Move the view forward by up to `rows` rows; returns whether it moved
+    pub fn scroll_down(&mut self, rows: usize) -> bool {
+        let offset = self.offset.saturating_sub(rows);
+        core::mem::replace(&mut self.offset, offset) != offset
+    }
+
+    /// Row `row` of the view, taken from the history or from the live `screen`
+    pub fn view_row<'a>(&'a self, screen: &'a Buffer, row: usize) -> &'a Row {
+        // 历史行和屏幕行首尾相接，视图是其中向上偏移offset行的一屏
+        let index = self.rows.len() - self.offset + row;
+        match self.rows.get(index) {
+            Some(history_row) => history_row,
+            None => &screen.chars[index - self.rows.len()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::{Color, ColorCode, BLANK, BUFFER_HEIGHT};
+
+    fn row(c: u8) -> Row {
+        [ScreenChar { ascii_character: c, color_code: ColorCode::new(Color::Green, Color::Black) }; BUFFER_WIDTH]
+    }
+
+    #[test_case]
+    fn full_scrollback_keeps_the_view_in_place() {
+        let screen = Buffer { chars: [[BLANK; BUFFER_WIDTH]; BUFFER_HEIGHT] };
+        let mut scrollback = Scrollback::new(4);
+        for c in b'a'..=b'd' {
+            scrollback.push(row(c));
+        }
+        scrollback.scroll_up(2);
+        let before: [Row; 2] = [*scrollback.view_row(&screen, 0), *scrollback.view_row(&screen, 1)];
+
+        scrollback.push(row(b'e'));
+        scrollback.push(row(b'f'));
+
+        assert_eq!(scrollback.len(), 4);
+        assert_eq!(*scrollback.view_row(&screen, 0), before[0]);
+        assert_eq!(*scrollback.view_row(&screen, 1), before[1]);
+    }
+}
@@ -1,5 +1,7 @@
-use volatile::Volatile;
 use core::fmt;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use volatile::Volatile;
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -44,69 +46,91 @@ struct ScreenChar {
 const BUFFER_HEIGHT: usize = 25;
 const BUFFER_WIDTH: usize = 80;
 
-// 双缓冲实现
+/// VGA文本缓冲区的内存布局，每个字符都用volatile访问，避免写入被优化掉
 #[repr(transparent)]
-pub struct DoubleBuffer {
-    chars: [[ScreenChar; BUFFER_WIDTH]; BUFFER_HEIGHT],
+struct Buffer {
+    chars: [[Volatile<ScreenChar>; BUFFER_WIDTH]; BUFFER_HEIGHT],
 }
 
-impl DoubleBuffer {
-    pub const fn new() -> Self {
-        DoubleBuffer {
-            chars: [[ScreenChar {
-                ascii_character: b' ',
-                color_code: ColorCode::new(Color::Black, Color::Black),
-            }; BUFFER_WIDTH]; BUFFER_HEIGHT],
-        }
-    }
+/// 直接写VGA文本缓冲区的输出，总是写在最后一行，换行时整屏上滚
+///
+/// 不分配内存，也不依赖终端，启动早期和异常处理中都可以使用。
+pub struct Writer {
+    column_position: usize,
+    color_code: ColorCode,
+    buffer: &'static mut Buffer,
+}
 
-    pub fn clear(&mut self) {
-        for row in 0..BUFFER_HEIGHT {
-            for col in 0..BUFFER_WIDTH {
-                self.chars[row][col] = ScreenChar {
-                    ascii_character: b' ',
-                    color_code: ColorCode::new(Color::Black, Color::Black),
-                };
-            }
+impl Writer {
+    pub fn new() -> Self {
+        Writer {
+            column_position: 0,
+            color_code: ColorCode::new(Color::Green, Color::Black),
+            buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
         }
     }
 
-    pub fn write_char(&mut self, row: usize, col: usize, char: u8, color_code: ColorCode) {
-        if row < BUFFER_HEIGHT && col < BUFFER_WIDTH {
-            self.chars[row][col] = ScreenChar {
-                ascii_character: char,
-                color_code,
-            };
+    pub fn write_byte(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.new_line(),
+            byte => {
+                if self.column_position >= BUFFER_WIDTH {
+                    self.new_line();
+                }
+                let row = BUFFER_HEIGHT - 1;
+                let col = self.column_position;
+                self.buffer.chars[row][col].write(ScreenChar {
+                    ascii_character: byte,
+                    color_code: self.color_code,
+                });
+                self.column_position += 1;
+            }
         }
     }
 
-    pub fn flush_to_vga(&self) {
-        let vga_buffer = unsafe { &mut *(0xb8000 as *mut [[ScreenChar; BUFFER_WIDTH]; BUFFER_HEIGHT]) };
-        for row in 0..BUFFER_HEIGHT {
-            for col in 0..BUFFER_WIDTH {
-                volatile::Volatile::write(&mut vga_buffer[row][col], self.chars[row][col]);
+    pub fn write_string(&mut self, s: &str) {
+        for byte in s.bytes() {
+            match byte {
+                // 可打印ASCII字符或换行
+                0x20..=0x7e | b'\n' => self.write_byte(byte),
+                // 其他字节显示为■
+                _ => self.write_byte(0xfe),
             }
         }
     }
 
-    pub fn scroll_up(&mut self) {
+    fn new_line(&mut self) {
         // 将所有行向上滚动一行
         for row in 1..BUFFER_HEIGHT {
             for col in 0..BUFFER_WIDTH {
-                self.chars[row - 1][col] = self.chars[row][col];
+                let character = self.buffer.chars[row][col].read();
+                self.buffer.chars[row - 1][col].write(character);
             }
         }
 
         // 清除最后一行
         for col in 0..BUFFER_WIDTH {
-            self.chars[BUFFER_HEIGHT - 1][col] = ScreenChar {
+            self.buffer.chars[BUFFER_HEIGHT - 1][col].write(ScreenChar {
                 ascii_character: b' ',
-                color_code: ColorCode::new(Color::Black, Color::Black),
-            };
+                color_code: self.color_code,
+            });
         }
+        self.column_position = 0;
+    }
+}
+
+impl fmt::Write for Writer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_string(s);
+        Ok(())
     }
 }
 
+lazy_static! {
+    /// 全局输出，print!和println!共用，保证连续的print!接在同一行
+    pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer::new());
+}
+
 /// Prints the given formatted string to the VGA text buffer
 /// through the global `WRITER` instance.
 #[macro_export]
@@ -122,11 +146,35 @@ macro_rules! println {
     ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
 }
 
+/// Prints from exception handlers, the panic handler and the allocation error
+/// handler, appending a newline.
+///
+/// Never allocates and never waits for the `WRITER` lock, which the interrupted
+/// code may be holding.
+#[macro_export]
+macro_rules! fault_println {
+    () => ($crate::vga_buffer::_fault_print(format_args!("\n")));
+    ($($arg:tt)*) => ($crate::vga_buffer::_fault_print(format_args!("{}\n", format_args!($($arg)*))));
+}
+
+#[doc(hidden)]
+pub fn _fault_print(args: fmt::Arguments) {
+    use core::fmt::Write;
+
+    // 锁被占用时用一个临时Writer直接写屏，宁可和被打断的输出交错也不能死锁
+    match WRITER.try_lock() {
+        Some(mut writer) => writer.write_fmt(args).unwrap(),
+        None => Writer::new().write_fmt(args).unwrap(),
+    }
+}
+
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     use core::fmt::Write;
-    use crate::terminal::Terminal;
-    let mut terminal = Terminal::new();
-    terminal.write_fmt(args).unwrap();
-    terminal.flush(); // 确保输出立即显示
+    use x86_64::instructions::interrupts;
+
+    // 关中断持锁，中断处理中的输出不会和被打断的输出争同一把锁
+    interrupts::without_interrupts(|| {
+        WRITER.lock().write_fmt(args).unwrap();
+    });
 }
\ No newline at end of file